@@ -1,12 +1,17 @@
 .extern kernel_main
 
 .global _start
+.global stack_guard
+.global stack_bottom
 .global stack_top
 .global GDT_end
 
 
 .set MB_MAGIC, 0x1BADB002          
-.set MB_FLAGS, 0
+.set MB_PAGE_ALIGN, 1 << 0         /* load modules on page boundaries */
+.set MB_MEMORY_INFO, 1 << 1        /* provide the memory map */
+.set MB_VIDEO_MODE, 1 << 2         /* provide the framebuffer, in the mode requested below */
+.set MB_FLAGS, MB_PAGE_ALIGN | MB_MEMORY_INFO | MB_VIDEO_MODE
 .set MB_CHECKSUM, (0 - (MB_MAGIC + MB_FLAGS))
 
 .section .multiboot
@@ -14,25 +19,93 @@
 	.long MB_MAGIC
 	.long MB_FLAGS
 	.long MB_CHECKSUM
+	.long 0, 0, 0, 0, 0            /* address fields, only used with flag 16 */
+	.long 1                        /* mode type: EGA text */
+	.long 80                       /* width in characters */
+	.long 25                       /* height in characters */
+	.long 0                        /* depth, 0 in text mode */
+
+.set MB2_MAGIC, 0xE85250D6
+.set MB2_ARCHITECTURE, 0           /* i386 protected mode */
+.set MB2_HEADER_LENGTH, (mb2_header_end - mb2_header_start)
+.set MB2_CHECKSUM, (0 - (MB2_MAGIC + MB2_ARCHITECTURE + MB2_HEADER_LENGTH))
+
+.section .multiboot2
+	.align 8
+	mb2_header_start:
+	.long MB2_MAGIC
+	.long MB2_ARCHITECTURE
+	.long MB2_HEADER_LENGTH
+	.long MB2_CHECKSUM
+	.align 8
+	.short 0, 0                    /* end tag: type, flags */
+	.long 8                        /* end tag: size */
+	mb2_header_end:
+
+.set KERNEL_OFFSET, 0xC0000000     /* virtual address of physical 0, see assets/linker.ld */
+.set PDE_HUGE, 0x83                /* present, writable, 4 MiB page */
+.set IDENTITY_ENTRIES, 768         /* 4 MiB pages mapping the lower 3 GiB onto themselves */
+.set KERNEL_ENTRY, 768             /* first directory entry of the higher half */
+.set CR4_PSE, 1 << 4               /* enables 4 MiB pages */
+.set CR0_PG, 1 << 31
+
+.ifndef KERNEL_STACK_SIZE          /* overridden with `as --defsym KERNEL_STACK_SIZE=...`, see the Makefile */
+.set KERNEL_STACK_SIZE, 64 * 1024  /* a multiple of 4 KiB */
+.endif
 
 .section .bss
 
-	.align 16
-	stack_bottom:
+	.align 4096
+	boot_page_directory:           /* replaced by the one built in memory::paging::init */
 		.skip 4096
+
+	.align 4096
+	stack_guard:                   /* left unmapped by memory::paging::init, an overflow faults here */
+		.skip 4096
+	stack_bottom:                  /* starts with the canary checked by stack::check */
+		.skip KERNEL_STACK_SIZE
 	stack_top:
 
- 
-.section .text
+/* Runs at the physical load address with paging disabled, so every higher half symbol is
+ * translated with KERNEL_OFFSET. EAX and EBX hold the Multiboot magic and info and are kept. */
+.section .boot.text, "ax"
 	_start:
+		mov $(boot_page_directory - KERNEL_OFFSET), %edi
+
+		xor %ecx, %ecx
+	identity_map:
+		mov %ecx, %edx
+		shl $22, %edx
+		or $PDE_HUGE, %edx
+		mov %edx, (%edi, %ecx, 4)
+		inc %ecx
+		cmp $IDENTITY_ENTRIES, %ecx
+		jb identity_map
+
+		movl $(0x000000 | PDE_HUGE), (KERNEL_ENTRY * 4)(%edi)       /* 0xC0000000 -> 0 */
+		movl $(0x400000 | PDE_HUGE), ((KERNEL_ENTRY + 1) * 4)(%edi) /* 0xC0400000 -> 4 MiB */
+
+		mov %cr4, %edx
+		or $CR4_PSE, %edx
+		mov %edx, %cr4
+		mov %edi, %cr3
+		mov %cr0, %edx
+		or $CR0_PG, %edx
+		mov %edx, %cr0
+
+		lea higher_half, %edx
+		jmp *%edx
+
+.section .text
+	higher_half:
 		mov $stack_top, %esp
 
-        push %eax
-        push %ebx
+		push %ebx                  /* info, second argument of kernel_main */
+		push %eax                  /* magic, first argument */
 		cli
 		call kernel_main
- 
+
 		hang:
-			cli      
-			hlt      
+			cli
+			hlt
 			jmp hang
@@ -0,0 +1,228 @@
+use crate::terminal::{layout::KeyboardLayout, vga::Color, MAX_SCREENS};
+
+/// One whitespace separated argument of the kernel command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arg<'a> {
+    pub key: &'a str,
+    /// The text after the first `=`, `None` for a flag.
+    pub value: Option<&'a str>,
+}
+
+impl<'a> Arg<'a> {
+    fn parse(arg: &'a str) -> Self {
+        match arg.split_once('=') {
+            Some((key, value)) => Arg { key, value: Some(value) },
+            None => Arg { key: arg, value: None },
+        }
+    }
+}
+
+/// The command line passed by the boot loader, e.g. `layout=fr loglevel=debug serial`.
+///
+/// GRUB puts the path of the kernel in front of the arguments when booting with `multiboot`,
+/// it is parsed as a flag that no accessor asks for.
+#[derive(Debug, Clone, Copy)]
+pub struct Cmdline<'a> {
+    raw: &'a str,
+}
+
+impl<'a> Cmdline<'a> {
+    pub fn new(raw: &'a str) -> Self {
+        Cmdline { raw }
+    }
+
+    /// Returns the arguments in the order they were given.
+    pub fn args(&self) -> impl Iterator<Item = Arg<'a>> {
+        self.raw.split_ascii_whitespace().map(Arg::parse)
+    }
+
+    /// Returns the value of the last `key=value` argument for `key`, so later arguments override earlier ones.
+    #[allow(dead_code)]
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.args().filter(|arg| arg.key == key).filter_map(|arg| arg.value).last()
+    }
+
+    /// Returns `true` if `key` was given as a flag, without a value.
+    #[allow(dead_code)]
+    pub fn has_flag(&self, key: &str) -> bool {
+        self.args().any(|arg| arg.key == key && arg.value.is_none())
+    }
+
+    /// Returns the value of `key` as a decimal number, or a hexadecimal one with a `0x` prefix.
+    ///
+    /// ### Returns:
+    /// - `None` if the argument is missing.
+    /// - `Some(Err(value))` if it is not a number.
+    #[allow(dead_code)]
+    pub fn number(&self, key: &str) -> Option<Result<u32, &'a str>> {
+        let value = self.get(key)?;
+
+        Some(parse_number(value).ok_or(value))
+    }
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Verbosity of the boot messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// Settings chosen on the kernel command line. Missing or invalid arguments keep the default.
+///
+/// - `layout=<name>`: Keyboard layout, `us` (default), `fr` or `de`.
+/// - `loglevel=<level>`: `error`, `warn`, `info` (default) or `debug`, which adds the memory map.
+/// - `serial`: Mirror the console on the first serial port.
+/// - `color=<attribute>`: VGA attribute of the text, e.g. `0x1F` for white on blue.
+/// - `screens=<count>`: Number of screens `Tab` cycles through, from 1 to `MAX_SCREENS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootOptions {
+    pub layout: KeyboardLayout,
+    pub log_level: LogLevel,
+    pub serial: bool,
+    pub color: u8,
+    pub screens: usize,
+}
+
+impl BootOptions {
+    pub const fn default() -> Self {
+        BootOptions {
+            layout: KeyboardLayout::Us,
+            log_level: LogLevel::Info,
+            serial: false,
+            color: Color::Default as u8,
+            screens: MAX_SCREENS,
+        }
+    }
+
+    /// Reads the options from `cmdline`.
+    ///
+    /// ### Parameters:
+    /// - `cmdline`: The kernel command line.
+    /// - `on_invalid`: Called with every known argument whose value is invalid, to report it.
+    pub fn parse<'a>(cmdline: &Cmdline<'a>, mut on_invalid: impl FnMut(Arg<'a>)) -> Self {
+        let mut options = BootOptions::default();
+
+        for arg in cmdline.args() {
+            let valid = match (arg.key, arg.value) {
+                ("layout", Some(name)) => KeyboardLayout::from_name(name).map(|layout| options.layout = layout).is_some(),
+                ("loglevel", Some(name)) => LogLevel::from_name(name).map(|level| options.log_level = level).is_some(),
+                ("serial", None) => {
+                    options.serial = true;
+                    true
+                }
+                ("color", Some(value)) => match parse_number(value) {
+                    Some(color @ 0..=0xFF) => {
+                        options.color = color as u8;
+                        true
+                    }
+                    _ => false,
+                },
+                ("screens", Some(value)) => match parse_number(value) {
+                    Some(count) if (1..=MAX_SCREENS as u32).contains(&count) => {
+                        options.screens = count as usize;
+                        true
+                    }
+                    _ => false,
+                },
+                ("layout" | "loglevel" | "serial" | "color" | "screens", _) => false,
+                _ => true,
+            };
+
+            if !valid {
+                on_invalid(arg);
+            }
+        }
+
+        options
+    }
+}
+
+#[cfg(test)]
+mod cmdline_test {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let cmdline = Cmdline::new("/boot/kernel.bin  layout=fr debug  a=b=c");
+        let mut args = cmdline.args();
+
+        assert_eq!(
+            args.next(),
+            Some(Arg {
+                key: "/boot/kernel.bin",
+                value: None
+            })
+        );
+        assert_eq!(
+            args.next(),
+            Some(Arg {
+                key: "layout",
+                value: Some("fr")
+            })
+        );
+        assert_eq!(args.next(), Some(Arg { key: "debug", value: None }));
+        assert_eq!(args.next(), Some(Arg { key: "a", value: Some("b=c") }));
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let cmdline = Cmdline::new("screens=2 quiet screens=0x3 color=blue");
+
+        assert_eq!(cmdline.get("screens"), Some("0x3"));
+        assert_eq!(cmdline.number("screens"), Some(Ok(3)));
+        assert_eq!(cmdline.number("color"), Some(Err("blue")));
+        assert_eq!(cmdline.number("missing"), None);
+        assert!(cmdline.has_flag("quiet"));
+        assert!(!cmdline.has_flag("screens"));
+    }
+
+    #[test]
+    fn test_boot_options() {
+        let cmdline = Cmdline::new("/boot/kernel.bin layout=de loglevel=debug serial color=0x1F screens=2");
+        let options = BootOptions::parse(&cmdline, |arg| panic!("{arg:?} is valid"));
+
+        assert_eq!(
+            options,
+            BootOptions {
+                layout: KeyboardLayout::German,
+                log_level: LogLevel::Debug,
+                serial: true,
+                color: 0x1F,
+                screens: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_boot_options_keep_defaults() {
+        let cmdline = Cmdline::new("layout=qwertz loglevel serial=1 color=0x100 screens=0 screens=9");
+        let mut invalid = 0;
+        let options = BootOptions::parse(&cmdline, |_| invalid += 1);
+
+        assert_eq!(invalid, 6);
+        assert_eq!(options, BootOptions::default());
+    }
+}
@@ -0,0 +1,329 @@
+use core::{
+    arch::asm,
+    mem::size_of,
+    ptr::{addr_of, addr_of_mut},
+};
+
+pub mod tss;
+
+/// Number of descriptors in the `GDT`.
+pub const GDT_LIMIT: usize = 7;
+
+/// Index of the TSS descriptor, filled in by `load` once the address of the TSS is known.
+const TSS_INDEX: u16 = 5;
+
+/// Index of the double fault TSS descriptor, filled in by `load` like `TSS_INDEX`.
+const DOUBLE_FAULT_TSS_INDEX: u16 = 6;
+
+/// Selector of the ring 0 code segment (`GDT[1]`).
+pub const KERNEL_CODE_SELECTOR: SegmentSelector = SegmentSelector::new(1, 0);
+
+/// Selector of the ring 0 data segment (`GDT[2]`).
+pub const KERNEL_DATA_SELECTOR: SegmentSelector = SegmentSelector::new(2, 0);
+
+/// Selector of the ring 3 code segment (`GDT[3]`), requested with RPL 3.
+#[allow(dead_code)]
+pub const USER_CODE_SELECTOR: SegmentSelector = SegmentSelector::new(3, 3);
+
+/// Selector of the ring 3 data segment (`GDT[4]`), requested with RPL 3.
+#[allow(dead_code)]
+pub const USER_DATA_SELECTOR: SegmentSelector = SegmentSelector::new(4, 3);
+
+/// Selector of the kernel Task State Segment (`GDT[5]`).
+pub const TSS_SELECTOR: SegmentSelector = SegmentSelector::new(TSS_INDEX, 0);
+
+/// Selector of the Task State Segment the double fault task gate switches to (`GDT[6]`).
+pub const DOUBLE_FAULT_TSS_SELECTOR: SegmentSelector = SegmentSelector::new(DOUBLE_FAULT_TSS_INDEX, 0);
+
+/// The Global Descriptor Table: flat 4 GiB code and data segments for ring 0 and ring 3, the kernel TSS
+/// and the double fault TSS.
+///
+/// It lives in writable memory because the TSS descriptors are patched in at runtime and `ltr` sets their busy bit.
+#[no_mangle]
+static mut GDT: [Descriptor; GDT_LIMIT] = [
+    Descriptor::NULL,
+    Descriptor::new(0, 0xFFFFF, Access::code(), Flags::flat()),
+    Descriptor::new(0, 0xFFFFF, Access::data(), Flags::flat()),
+    Descriptor::new(0, 0xFFFFF, Access::code().ring(3), Flags::flat()),
+    Descriptor::new(0, 0xFFFFF, Access::data().ring(3), Flags::flat()),
+    Descriptor::NULL,
+    Descriptor::NULL,
+];
+
+/// Loads `GDT` into the GDTR, reloads every segment register so the CPU no longer runs
+/// on the descriptors GRUB left behind, and loads the kernel TSS into the task register.
+///
+/// `CS` can only be changed through a far control transfer, so it is reloaded with a far return
+/// to the next instruction. `DS`, `ES`, `FS`, `GS` and `SS` are set to `KERNEL_DATA_SELECTOR`.
+///
+/// ## SAFETY
+/// Must run in ring 0 with interrupts disabled, and only once (see `tss::load`). Every selector currently
+/// in use has to remain valid in the new table, which holds for the flat kernel segments defined above.
+pub unsafe fn load() {
+    (*addr_of_mut!(GDT))[TSS_INDEX as usize] = tss::descriptor();
+    (*addr_of_mut!(GDT))[DOUBLE_FAULT_TSS_INDEX as usize] = tss::double_fault_descriptor();
+
+    let pointer = DescriptorTablePointer {
+        limit: (size_of::<[Descriptor; GDT_LIMIT]>() - 1) as u16,
+        base: addr_of!(GDT) as usize,
+    };
+
+    asm!("lgdt [{}]", in(reg) &pointer, options(readonly, nostack, preserves_flags));
+    reload_segments();
+    tss::load();
+}
+
+/// Reads back the active GDTR with `sgdt`.
+///
+/// ### Returns:
+/// The base address and limit of the descriptor table the CPU is currently using.
+#[allow(dead_code)]
+pub fn current() -> DescriptorTablePointer {
+    let mut pointer = DescriptorTablePointer { limit: 0, base: 0 };
+
+    unsafe { asm!("sgdt [{}]", in(reg) &mut pointer, options(nostack, preserves_flags)) };
+
+    pointer
+}
+
+/// Returns `true` if the GDTR points at `GDT` with the expected limit and `CS` holds `KERNEL_CODE_SELECTOR`.
+#[allow(dead_code)]
+pub fn is_loaded() -> bool {
+    let pointer = current();
+    let (base, limit) = (pointer.base, pointer.limit);
+    let code_selector: u16;
+
+    unsafe { asm!("mov {:x}, cs", out(reg) code_selector, options(nomem, nostack, preserves_flags)) };
+
+    base == addr_of!(GDT) as usize && limit as usize == size_of::<[Descriptor; GDT_LIMIT]>() - 1 && code_selector == KERNEL_CODE_SELECTOR.bits()
+}
+
+/// Reloads `CS` with a far return to the next instruction, then loads the data segment registers.
+///
+/// The far return only assembles for 32-bit protected mode, host builds (used by `cargo test`) skip it.
+unsafe fn reload_segments() {
+    #[cfg(target_arch = "x86")]
+    asm!(
+        "push {code}",
+        "lea {tmp}, [2f]",
+        "push {tmp}",
+        "retf",
+        "2:",
+        code = const KERNEL_CODE_SELECTOR.bits(),
+        tmp = out(reg) _,
+    );
+
+    asm!(
+        "mov ds, {0:x}",
+        "mov es, {0:x}",
+        "mov fs, {0:x}",
+        "mov gs, {0:x}",
+        "mov ss, {0:x}",
+        in(reg) KERNEL_DATA_SELECTOR.bits() as u32,
+        options(nostack, preserves_flags),
+    );
+}
+
+/// A segment selector: the index of a descriptor in the GDT and the requested privilege level (RPL).
+///
+/// ```text
+/// 15                3   2   1 0
+/// +-----------------+---+-----+
+/// |      index      | 0 | RPL |
+/// +-----------------+---+-----+
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct SegmentSelector(u16);
+
+impl SegmentSelector {
+    /// Creates a GDT selector for the descriptor at `index`, requested with privilege level `rpl`.
+    pub const fn new(index: u16, rpl: u8) -> Self {
+        SegmentSelector(index << 3 | (rpl & 0b11) as u16)
+    }
+
+    /// Returns the raw `u16` that is loaded into a segment register.
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+}
+
+/// The operand of `lgdt`/`sgdt` (and `lidt`/`sidt`): the size of the table minus one and its linear address.
+#[repr(C, packed)]
+pub struct DescriptorTablePointer {
+    pub limit: u16,
+    pub base: usize,
+}
+
+/// A single 8-byte segment descriptor.
+///
+/// The base and limit are scattered across the descriptor for historical reasons, `Descriptor::new`
+/// takes care of placing every field at the right bit offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Descriptor(u64);
+
+impl Descriptor {
+    /// The mandatory null descriptor at index 0.
+    pub const NULL: Descriptor = Descriptor(0);
+
+    /// Encodes a segment descriptor.
+    ///
+    /// ### Parameters:
+    /// - `base`: The linear address where the segment begins.
+    /// - `limit`: The 20-bit segment limit, in bytes or in 4 KiB pages depending on `flags`.
+    /// - `access`: The access byte describing the segment type and privilege level.
+    /// - `flags`: The granularity and size flags.
+    pub const fn new(base: u32, limit: u32, access: Access, flags: Flags) -> Self {
+        let base = base as u64;
+        let limit = limit as u64;
+
+        Descriptor(
+            (limit & 0xFFFF)
+                | (base & 0xFF_FFFF) << 16
+                | (access.bits() as u64) << 40
+                | ((limit >> 16) & 0xF) << 48
+                | (flags.bits() as u64 & 0xF) << 52
+                | ((base >> 24) & 0xFF) << 56,
+        )
+    }
+
+    /// Returns the raw `u64` representation of the descriptor.
+    #[allow(dead_code)]
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Builder for the access byte of a segment descriptor.
+///
+/// See the [OSDev wiki](https://wiki.osdev.org/Global_Descriptor_Table#Segment_Descriptor) for the meaning of each bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access(u8);
+
+#[allow(dead_code)]
+impl Access {
+    const ACCESSED: u8 = 1 << 0;
+    const READ_WRITE: u8 = 1 << 1;
+    const DIRECTION_CONFORMING: u8 = 1 << 2;
+    const EXECUTABLE: u8 = 1 << 3;
+    const CODE_OR_DATA: u8 = 1 << 4;
+    const RING_SHIFT: u8 = 5;
+    const PRESENT: u8 = 1 << 7;
+    const TSS_AVAILABLE: u8 = 0x9;
+
+    /// A present, readable, ring 0 code segment.
+    pub const fn code() -> Self {
+        Access(Self::PRESENT | Self::CODE_OR_DATA | Self::EXECUTABLE | Self::READ_WRITE)
+    }
+
+    /// A present, writable, ring 0 data segment.
+    pub const fn data() -> Self {
+        Access(Self::PRESENT | Self::CODE_OR_DATA | Self::READ_WRITE)
+    }
+
+    /// A present, available (not busy) 32-bit TSS system segment.
+    pub const fn tss() -> Self {
+        Access(Self::PRESENT | Self::TSS_AVAILABLE)
+    }
+
+    /// Sets the descriptor privilege level. Only the lowest two bits of `ring` are used.
+    pub const fn ring(self, ring: u8) -> Self {
+        Access(self.0 & !(0b11 << Self::RING_SHIFT) | (ring & 0b11) << Self::RING_SHIFT)
+    }
+
+    /// Makes a code segment conforming, or a data segment grow down.
+    pub const fn conforming(self) -> Self {
+        Access(self.0 | Self::DIRECTION_CONFORMING)
+    }
+
+    /// Pre-sets the accessed bit so the CPU never has to write to the table.
+    pub const fn accessed(self) -> Self {
+        Access(self.0 | Self::ACCESSED)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+/// Builder for the 4-bit flags nibble of a segment descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Flags(u8);
+
+#[allow(dead_code)]
+impl Flags {
+    const LONG_MODE: u8 = 1 << 1;
+    const PROTECTED_MODE: u8 = 1 << 2;
+    const PAGE_GRANULARITY: u8 = 1 << 3;
+
+    /// A 32-bit segment whose limit is counted in 4 KiB pages, so a limit of `0xFFFFF` spans 4 GiB.
+    pub const fn flat() -> Self {
+        Flags(Self::PROTECTED_MODE | Self::PAGE_GRANULARITY)
+    }
+
+    /// No flags set, as required for system segments like the TSS.
+    pub const fn system() -> Self {
+        Flags(0)
+    }
+
+    /// A 32-bit segment whose limit is counted in bytes.
+    pub const fn byte_granular() -> Self {
+        Flags(Self::PROTECTED_MODE)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod descriptor_test {
+    use super::*;
+
+    #[test]
+    fn test_kernel_code_segment_matches_reference_encoding() {
+        assert_eq!(Descriptor::new(0, 0xFFFFF, Access::code(), Flags::flat()).bits(), 0x00CF9A000000FFFF);
+    }
+
+    #[test]
+    fn test_kernel_data_segment_matches_reference_encoding() {
+        assert_eq!(Descriptor::new(0, 0xFFFFF, Access::data(), Flags::flat()).bits(), 0x00CF92000000FFFF);
+    }
+
+    #[test]
+    fn test_user_segments_match_reference_encoding() {
+        assert_eq!(Descriptor::new(0, 0xFFFFF, Access::code().ring(3), Flags::flat()).bits(), 0x00CFFA000000FFFF);
+        assert_eq!(Descriptor::new(0, 0xFFFFF, Access::data().ring(3), Flags::flat()).bits(), 0x00CFF2000000FFFF);
+    }
+
+    #[test]
+    fn test_selectors_encode_index_and_rpl() {
+        assert_eq!(KERNEL_CODE_SELECTOR.bits(), 0x08);
+        assert_eq!(KERNEL_DATA_SELECTOR.bits(), 0x10);
+        assert_eq!(USER_CODE_SELECTOR.bits(), 0x1B);
+        assert_eq!(USER_DATA_SELECTOR.bits(), 0x23);
+        assert_eq!(TSS_SELECTOR.bits(), 0x28);
+        assert_eq!(DOUBLE_FAULT_TSS_SELECTOR.bits(), 0x30);
+    }
+
+    #[test]
+    fn test_tss_descriptor_covers_the_whole_segment() {
+        assert_eq!(core::mem::size_of::<tss::TaskStateSegment>(), 104);
+        assert_eq!(tss::TaskStateSegment::descriptor(0x0010_2000).bits(), 0x0000_8910_2000_0067);
+    }
+
+    #[test]
+    fn test_base_and_limit_are_split_across_the_descriptor() {
+        let d = Descriptor::new(0x12345678, 0xABCDE, Access::data(), Flags::byte_granular());
+
+        assert_eq!(d.bits(), 0x124A_9234_5678_BCDE);
+    }
+
+    #[test]
+    fn test_ring_sets_descriptor_privilege_level() {
+        assert_eq!(Access::code().ring(3).bits(), 0xFA);
+        assert_eq!(Access::data().ring(3).bits(), 0xF2);
+        assert_eq!(Access::data().ring(3).ring(0).bits(), 0x92);
+    }
+}
@@ -0,0 +1,192 @@
+use core::{
+    arch::asm,
+    mem::size_of,
+    ptr::{addr_of, addr_of_mut},
+};
+
+use super::{Access, Descriptor, Flags, SegmentSelector, KERNEL_CODE_SELECTOR, KERNEL_DATA_SELECTOR, TSS_SELECTOR};
+
+/// The kernel's Task State Segment.
+///
+/// With software task switching the CPU only reads `ss0:esp0` from it, to find the kernel stack
+/// when an interrupt or a call gate raises the privilege level from ring 3 to ring 0.
+static mut TSS: TaskStateSegment = TaskStateSegment::new();
+
+/// Size of the stack the double fault task runs on. The `Terminal` the crash report is written to takes
+/// over 20 KiB of it by itself.
+const DOUBLE_FAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// `EFLAGS` of the double fault task: only the reserved bit 1 is set, so it runs with interrupts disabled.
+const DOUBLE_FAULT_EFLAGS: u32 = 1 << 1;
+
+/// The TSS the double fault task gate switches to, set up by `init_double_fault_task`.
+///
+/// The switch loads every register from it, so the handler gets a known good stack even when the
+/// double fault was caused by the kernel stack running into its guard page.
+static mut DOUBLE_FAULT_TSS: TaskStateSegment = TaskStateSegment::new();
+
+#[repr(C, align(16))]
+struct Stack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+static mut DOUBLE_FAULT_STACK: Stack = Stack([0; DOUBLE_FAULT_STACK_SIZE]);
+
+/// A 32-bit [Task State Segment](https://wiki.osdev.org/Task_State_Segment).
+///
+/// Every field the CPU saves or restores during a hardware task switch is present, so the same
+/// structure can back a task gate later on. Segment fields are stored as `u32` with the upper half reserved.
+#[derive(Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    pub link: u32,
+    pub esp0: u32,
+    pub ss0: u32,
+    pub esp1: u32,
+    pub ss1: u32,
+    pub esp2: u32,
+    pub ss2: u32,
+    pub cr3: u32,
+    pub eip: u32,
+    pub eflags: u32,
+    pub eax: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub ebx: u32,
+    pub esp: u32,
+    pub ebp: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub es: u32,
+    pub cs: u32,
+    pub ss: u32,
+    pub ds: u32,
+    pub fs: u32,
+    pub gs: u32,
+    pub ldtr: u32,
+    pub trap: u16,
+    pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    /// Creates an empty TSS whose I/O permission bitmap lies past its limit, denying ring 3 any port access.
+    pub const fn new() -> Self {
+        TaskStateSegment {
+            link: 0,
+            esp0: 0,
+            ss0: 0,
+            esp1: 0,
+            ss1: 0,
+            esp2: 0,
+            ss2: 0,
+            cr3: 0,
+            eip: 0,
+            eflags: 0,
+            eax: 0,
+            ecx: 0,
+            edx: 0,
+            ebx: 0,
+            esp: 0,
+            ebp: 0,
+            esi: 0,
+            edi: 0,
+            es: 0,
+            cs: 0,
+            ss: 0,
+            ds: 0,
+            fs: 0,
+            gs: 0,
+            ldtr: 0,
+            trap: 0,
+            iomap_base: size_of::<TaskStateSegment>() as u16,
+        }
+    }
+
+    /// Builds the GDT descriptor pointing at a TSS located at `base`.
+    pub const fn descriptor(base: u32) -> Descriptor {
+        Descriptor::new(base, size_of::<TaskStateSegment>() as u32 - 1, Access::tss(), Flags::system())
+    }
+}
+
+/// Returns the GDT descriptor of the kernel TSS.
+pub fn descriptor() -> Descriptor {
+    TaskStateSegment::descriptor(addr_of!(TSS) as u32)
+}
+
+/// Returns the GDT descriptor of the double fault TSS.
+pub fn double_fault_descriptor() -> Descriptor {
+    TaskStateSegment::descriptor(addr_of!(DOUBLE_FAULT_TSS) as u32)
+}
+
+/// Sets the stack the CPU switches to when entering ring 0 from a less privileged ring.
+///
+/// ### Parameters:
+/// - `ss0`: The stack segment selector, normally `KERNEL_DATA_SELECTOR`.
+/// - `esp0`: The initial stack pointer, i.e. the **top** of the kernel stack.
+///
+/// ## SAFETY
+/// `esp0` must point to the top of a stack that is not in use by any running code, otherwise the
+/// next privilege transition will overwrite it.
+#[allow(dead_code)]
+pub unsafe fn set_kernel_stack(ss0: SegmentSelector, esp0: u32) {
+    TSS.ss0 = ss0.bits() as u32;
+    TSS.esp0 = esp0;
+}
+
+/// Loads `TSS_SELECTOR` into the task register.
+///
+/// `esp0` is left at 0: the boot stack is the one the kernel runs on, so it cannot double as the
+/// stack of a privilege transition. Each user task gets its own kernel stack with `set_kernel_stack`.
+///
+/// ## SAFETY
+/// The GDT containing the TSS descriptor must already be loaded, and `load` must only be called once:
+/// `ltr` marks the descriptor busy and faults if it is loaded a second time.
+pub unsafe fn load() {
+    asm!("ltr {0:x}", in(reg) TSS_SELECTOR.bits(), options(nostack, preserves_flags));
+}
+
+/// Prepares the double fault TSS to start running `entry` on `DOUBLE_FAULT_STACK`, in ring 0 with the
+/// flat kernel segments and the page directory currently in `CR3`.
+///
+/// ### Notes:
+/// - The CPU pushes the error code on the new stack and jumps to `entry` without a return address,
+///   so `entry` sees the error code at `[esp]`.
+/// - `set_double_fault_page_directory` must be called whenever the kernel switches to another page directory.
+///
+/// ## SAFETY
+/// Must run before the double fault task gate can be used, and never while the double fault task is running.
+pub unsafe fn init_double_fault_task(entry: u32) {
+    let cr3: usize;
+    asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags));
+
+    let tss = &mut *addr_of_mut!(DOUBLE_FAULT_TSS);
+    let data = KERNEL_DATA_SELECTOR.bits() as u32;
+
+    tss.cr3 = cr3 as u32;
+    tss.eip = entry;
+    tss.eflags = DOUBLE_FAULT_EFLAGS;
+    tss.esp = addr_of!(DOUBLE_FAULT_STACK) as u32 + DOUBLE_FAULT_STACK_SIZE as u32;
+    tss.cs = KERNEL_CODE_SELECTOR.bits() as u32;
+    tss.ss = data;
+    tss.ds = data;
+    tss.es = data;
+    tss.fs = data;
+    tss.gs = data;
+}
+
+/// Sets the page directory the double fault task switches to.
+///
+/// ### Parameters:
+/// - `cr3`: The physical address of a page directory mapping the kernel image and the VGA buffer.
+///
+/// ## SAFETY
+/// `cr3` must stay valid for as long as a double fault can happen.
+pub unsafe fn set_double_fault_page_directory(cr3: u32) {
+    DOUBLE_FAULT_TSS.cr3 = cr3;
+}
+
+/// Returns a copy of the kernel TSS.
+///
+/// When the double fault task gate switches away from the kernel, the CPU saves the registers of the
+/// interrupted code in it, so from the double fault task this is the state at the time of the fault.
+pub fn kernel_task_state() -> TaskStateSegment {
+    unsafe { *addr_of!(TSS) }
+}
@@ -0,0 +1,308 @@
+use core::arch::asm;
+
+use super::{InterruptFrame, DOUBLE_FAULT_VECTOR};
+use crate::{
+    gdt::tss,
+    memory::paging,
+    print::{slice_to_str, u64_to_base},
+    stack,
+    terminal::{vga::Color, Terminal},
+};
+
+/// Human readable names of the CPU exceptions, indexed by vector.
+/// See the [OSDev wiki](https://wiki.osdev.org/Exceptions) for details on each one.
+pub const EXCEPTION_NAMES: [&str; super::EXCEPTION_VECTORS] = [
+    "Divide Error",
+    "Debug",
+    "Non-Maskable Interrupt",
+    "Breakpoint",
+    "Overflow",
+    "Bound Range Exceeded",
+    "Invalid Opcode",
+    "Device Not Available",
+    "Double Fault",
+    "Coprocessor Segment Overrun",
+    "Invalid TSS",
+    "Segment Not Present",
+    "Stack-Segment Fault",
+    "General Protection Fault",
+    "Page Fault",
+    "Reserved",
+    "x87 Floating-Point Exception",
+    "Alignment Check",
+    "Machine Check",
+    "SIMD Floating-Point Exception",
+    "Virtualization Exception",
+    "Control Protection Exception",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Hypervisor Injection Exception",
+    "VMM Communication Exception",
+    "Security Exception",
+    "Reserved",
+];
+
+/// Prints a crash report for the exception described by `frame` and halts the CPU.
+pub fn handle(frame: &InterruptFrame) -> ! {
+    let mut t = Terminal::default();
+
+    write_report(&mut t, frame, frame.interrupted_esp());
+    t.flush();
+
+    halt()
+}
+
+/// Writes the crash report for `frame` to `t`, without flushing it.
+///
+/// The report contains the exception name and vector, the error code, `EIP`, `CS`, `EFLAGS`
+/// and the general purpose registers as they were when the exception was raised.
+///
+/// ### Parameters:
+/// - `esp`: `ESP` of the interrupted code, which `frame.esp` is not when the frame was built by an entry stub.
+fn write_report(t: &mut Terminal, frame: &InterruptFrame, esp: u32) {
+    t.write_color_str("Exception: ", Color::Error as u8);
+    t.write_color_str(EXCEPTION_NAMES[frame.vector as usize], Color::Error as u8);
+    write_value(t, " #", frame.vector, 10);
+    t.write_str("\n\n");
+
+    write_register(t, "error code", frame.error_code);
+    t.write_str("\n");
+    write_register(t, "eip", frame.eip);
+    write_register(t, "cs", frame.cs);
+    write_register(t, "eflags", frame.eflags);
+    t.write_str("\n");
+    write_register(t, "eax", frame.eax);
+    write_register(t, "ebx", frame.ebx);
+    write_register(t, "ecx", frame.ecx);
+    write_register(t, "edx", frame.edx);
+    t.write_str("\n");
+    write_register(t, "esi", frame.esi);
+    write_register(t, "edi", frame.edi);
+    write_register(t, "ebp", frame.ebp);
+    write_register(t, "esp", esp);
+    t.write_str("\n");
+}
+
+/// Runs as the double fault task, on its own stack, with the error code pushed by the CPU as argument.
+///
+/// The registers of the faulting code were saved in the kernel TSS by the task switch. If the fault
+/// came from the guard page below the kernel stack, the report starts with the stack overflow details.
+#[no_mangle]
+extern "C" fn double_fault_handler(error_code: u32) -> ! {
+    let task = tss::kernel_task_state();
+    let frame = InterruptFrame {
+        edi: task.edi,
+        esi: task.esi,
+        ebp: task.ebp,
+        // No stub ran, the task switch saved `ESP` as it was at the time of the fault.
+        esp: task.esp,
+        ebx: task.ebx,
+        edx: task.edx,
+        ecx: task.ecx,
+        eax: task.eax,
+        vector: DOUBLE_FAULT_VECTOR as u32,
+        error_code,
+        eip: task.eip,
+        cs: task.cs,
+        eflags: task.eflags,
+    };
+    let mut t = Terminal::default();
+
+    // A push into the guard page faults with `esp` still on the stack, `CR2` holds the address it missed.
+    let fault_address = read_cr2();
+    if stack::is_guard_page(fault_address) || stack::is_guard_page(task.esp as usize) {
+        let address = if stack::is_guard_page(fault_address) {
+            fault_address
+        } else {
+            task.esp as usize
+        };
+
+        stack::write_overflow(&mut t, "double fault on the guard page below the stack", Some(address));
+        t.write_str("\n");
+    }
+    write_report(&mut t, &frame, task.esp);
+    t.flush();
+
+    halt()
+}
+
+/// The error code pushed by the CPU on a page fault.
+///
+/// See the [OSDev wiki](https://wiki.osdev.org/Exceptions#Page_Fault) for the meaning of each bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageFaultError(u32);
+
+impl PageFaultError {
+    const PRESENT: u32 = 1 << 0;
+    const WRITE: u32 = 1 << 1;
+    const USER: u32 = 1 << 2;
+    const RESERVED: u32 = 1 << 3;
+    const INSTRUCTION_FETCH: u32 = 1 << 4;
+
+    pub const fn new(bits: u32) -> Self {
+        PageFaultError(bits)
+    }
+
+    /// The page was present, the access broke its protection. Otherwise the page was not mapped.
+    pub const fn is_protection_violation(self) -> bool {
+        self.0 & Self::PRESENT != 0
+    }
+
+    pub const fn is_write(self) -> bool {
+        self.0 & Self::WRITE != 0
+    }
+
+    /// The access came from ring 3.
+    pub const fn is_user(self) -> bool {
+        self.0 & Self::USER != 0
+    }
+
+    /// The translation went through a page directory or page table entry with a reserved bit set,
+    /// whatever the access. Such an entry is corrupted.
+    pub const fn is_reserved_bit_violation(self) -> bool {
+        self.0 & Self::RESERVED != 0
+    }
+
+    /// Only reported with NX enabled, which needs PAE.
+    pub const fn is_instruction_fetch(self) -> bool {
+        self.0 & Self::INSTRUCTION_FETCH != 0
+    }
+
+    /// Returns the kind of access that faulted.
+    pub const fn access(self) -> &'static str {
+        if self.is_instruction_fetch() {
+            "instruction fetch"
+        } else if self.is_write() {
+            "write"
+        } else {
+            "read"
+        }
+    }
+
+    /// Returns why the access faulted.
+    pub const fn cause(self) -> &'static str {
+        if self.is_reserved_bit_violation() {
+            "reserved bit set in a page directory or page table entry"
+        } else if self.is_protection_violation() {
+            "protection violation"
+        } else {
+            "page not present"
+        }
+    }
+
+    pub const fn mode(self) -> &'static str {
+        if self.is_user() {
+            "user"
+        } else {
+            "kernel"
+        }
+    }
+}
+
+/// Accesses below this address are reported as null pointer dereferences. The first page is never
+/// mapped once `paging::init` has run, so dereferencing a null pointer, or a field of a null struct pointer, faults.
+const NULL_PAGE_END: usize = paging::PAGE_SIZE;
+
+/// Returns what a fault at `address` most likely comes from, as the start of its report.
+fn page_fault_kind(address: usize, in_guard_page: bool) -> &'static str {
+    if address < NULL_PAGE_END {
+        "null pointer dereference"
+    } else if in_guard_page {
+        "kernel stack overflow"
+    } else {
+        "page fault"
+    }
+}
+
+/// Reports a page fault through `panic!` with the faulting address from `CR2`, the decoded error code
+/// and the instruction that faulted.
+///
+/// ### Notes:
+/// - A stack overflow usually escalates to a double fault instead, since the CPU cannot push the
+///   page fault frame on a full stack. Only accesses to the guard page through a pointer end up here.
+pub fn page_fault(frame: &InterruptFrame) -> ! {
+    let address = read_cr2();
+    let error = PageFaultError::new(frame.error_code);
+    let in_guard_page = stack::is_guard_page(address);
+
+    panic!(
+        "{}: {} of 0x{:08x} from {} mode, {}{}\neip=0x{:08x} error code=0x{:x}",
+        page_fault_kind(address, in_guard_page),
+        error.access(),
+        address,
+        error.mode(),
+        error.cause(),
+        if in_guard_page { ", in the guard page below the stack" } else { "" },
+        frame.eip,
+        frame.error_code,
+    );
+}
+
+/// Returns `CR2`, the linear address of the last page fault.
+fn read_cr2() -> usize {
+    let address: usize;
+    unsafe { asm!("mov {}, cr2", out(reg) address, options(nomem, nostack, preserves_flags)) };
+    address
+}
+
+// The task switch jumps here with the error code on top of the stack. Calling the handler pushes a
+// return address above it, which turns the error code into its first argument.
+#[cfg(target_arch = "x86")]
+core::arch::global_asm!(
+    ".section .text",
+    ".global double_fault_entry",
+    "double_fault_entry:",
+    "    call double_fault_handler",
+);
+
+/// Writes `name=0x<value>` followed by a space.
+fn write_register(t: &mut Terminal, name: &str, value: u32) {
+    t.write_str(name);
+    write_value(t, "=0x", value, 16);
+    t.write_str(" ");
+}
+
+/// Writes `prefix` followed by `value` converted to `base`.
+fn write_value(t: &mut Terminal, prefix: &str, value: u32, base: u8) {
+    let (slice, len) = u64_to_base(value as u64, base).unwrap();
+
+    t.write_str(prefix);
+    t.write_str(slice_to_str((&slice, len)).unwrap());
+}
+
+/// Stops the CPU for good. Interrupts are disabled so only an NMI can wake it up, and it halts again.
+pub fn halt() -> ! {
+    loop {
+        unsafe { asm!("cli", "hlt", options(nomem, nostack)) };
+    }
+}
+
+#[cfg(test)]
+mod page_fault_test {
+    use super::*;
+
+    #[test]
+    fn test_error_code_decoding() {
+        let error = PageFaultError::new(0b00111);
+        assert!(error.is_protection_violation() && error.is_write() && error.is_user());
+        assert_eq!((error.access(), error.cause(), error.mode()), ("write", "protection violation", "user"));
+
+        let error = PageFaultError::new(0);
+        assert_eq!((error.access(), error.cause(), error.mode()), ("read", "page not present", "kernel"));
+
+        assert_eq!(PageFaultError::new(0b10000).access(), "instruction fetch");
+        assert_eq!(PageFaultError::new(0b01001).cause(), "reserved bit set in a page directory or page table entry");
+    }
+
+    #[test]
+    fn test_page_fault_kind() {
+        assert_eq!(page_fault_kind(0, false), "null pointer dereference");
+        assert_eq!(page_fault_kind(0xFFC, false), "null pointer dereference");
+        assert_eq!(page_fault_kind(0xC018_CFFC, true), "kernel stack overflow");
+        assert_eq!(page_fault_kind(0xD000_0000, false), "page fault");
+    }
+}
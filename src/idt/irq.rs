@@ -0,0 +1,35 @@
+use core::ptr::{addr_of, addr_of_mut};
+
+use super::InterruptFrame;
+use crate::pic::{self, Irq, IRQ_LINES, PIC_1_OFFSET};
+
+/// A handler for a hardware interrupt. The end of interrupt is sent by `handle` once it returns.
+pub type IrqHandler = fn(&mut InterruptFrame);
+
+/// Installed handlers, indexed by IRQ line.
+static mut HANDLERS: [Option<IrqHandler>; IRQ_LINES] = [None; IRQ_LINES];
+
+/// Installs `handler` for `irq`, then unmasks the line on the PIC.
+///
+/// ## SAFETY
+/// Must not be called from an interrupt handler, and `irq` must not be unmasked while its handler is replaced.
+#[allow(dead_code)]
+pub unsafe fn set_handler(irq: Irq, handler: IrqHandler) {
+    (*addr_of_mut!(HANDLERS))[irq as usize] = Some(handler);
+    pic::unmask(irq);
+}
+
+/// Filters out spurious interrupts, runs the handler installed for the line and acknowledges it.
+pub fn handle(frame: &mut InterruptFrame) {
+    let irq = (frame.vector - PIC_1_OFFSET as u32) as u8;
+
+    if pic::is_spurious(irq) {
+        return;
+    }
+
+    if let Some(handler) = unsafe { (*addr_of!(HANDLERS))[irq as usize] } {
+        handler(frame);
+    }
+
+    pic::end_of_interrupt(irq);
+}
@@ -0,0 +1,304 @@
+use core::{
+    arch::asm,
+    mem::{offset_of, size_of},
+    ptr::{addr_of, addr_of_mut},
+};
+
+use crate::{
+    gdt::{tss, DescriptorTablePointer, SegmentSelector, DOUBLE_FAULT_TSS_SELECTOR, KERNEL_CODE_SELECTOR},
+    pic::IRQ_LINES,
+};
+
+mod exceptions;
+pub mod irq;
+
+pub use exceptions::halt;
+
+/// Number of gates in the `IDT`, one per interrupt vector.
+pub const IDT_ENTRIES: usize = 256;
+
+/// Number of vectors reserved by the CPU for exceptions.
+pub const EXCEPTION_VECTORS: usize = 32;
+
+/// Number of vectors with an entry stub: the CPU exceptions followed by the remapped PIC lines.
+const STUB_VECTORS: usize = EXCEPTION_VECTORS + IRQ_LINES;
+
+/// Vector of the double fault exception, delivered through a task gate instead of its entry stub.
+const DOUBLE_FAULT_VECTOR: usize = 8;
+
+/// Vector of the page fault exception.
+const PAGE_FAULT_VECTOR: usize = 14;
+
+/// EFLAGS bit set while the CPU accepts maskable interrupts.
+const INTERRUPT_FLAG: usize = 1 << 9;
+
+/// The Interrupt Descriptor Table. Vectors without a handler hold a non-present gate,
+/// so raising them results in a General Protection Fault instead of jumping into garbage.
+static mut IDT: [Gate; IDT_ENTRIES] = [Gate::missing(); IDT_ENTRIES];
+
+extern "C" {
+    /// Addresses of the assembly entry stubs defined below, indexed by vector.
+    static isr_stub_table: [u32; STUB_VECTORS];
+    /// Entry point of the double fault task, defined in `exceptions.rs`.
+    fn double_fault_entry();
+}
+
+/// Registers saved by the entry stubs, in the order they end up on the stack.
+///
+/// `edi` to `eax` are pushed by `pushad`, `vector` and `error_code` by the stub itself
+/// (with a dummy `0` for vectors without an error code), `eip`, `cs` and `eflags` by the CPU.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct InterruptFrame {
+    pub edi: u32,
+    pub esi: u32,
+    pub ebp: u32,
+    /// `ESP` when `pushad` ran, which points at `vector`. See `interrupted_esp` for the value of the interrupted code.
+    pub esp: u32,
+    pub ebx: u32,
+    pub edx: u32,
+    pub ecx: u32,
+    pub eax: u32,
+    pub vector: u32,
+    pub error_code: u32,
+    pub eip: u32,
+    pub cs: u32,
+    pub eflags: u32,
+}
+
+impl InterruptFrame {
+    /// Returns `ESP` as it was when the exception was raised, right past what the stub and the CPU pushed.
+    ///
+    /// ### Notes:
+    /// - Only valid when the interrupted code ran in ring 0. From ring 3 the CPU switches to the kernel
+    ///   stack first and pushes the user `SS:ESP` after `eflags`.
+    pub fn interrupted_esp(&self) -> u32 {
+        self.esp + (size_of::<InterruptFrame>() - offset_of!(InterruptFrame, vector)) as u32
+    }
+}
+
+/// Fills the `IDT` with the exception and IRQ entry stubs and loads it with `lidt`.
+///
+/// The double fault is the exception: it goes through a task gate to `DOUBLE_FAULT_TSS_SELECTOR`, so that it
+/// gets a fresh stack even if the kernel stack is what caused it. An interrupt gate would push its frame on
+/// the broken stack, fault again and reset the machine with a triple fault.
+///
+/// ## SAFETY
+/// Must run in ring 0 after `gdt::load`, since every gate refers to `KERNEL_CODE_SELECTOR` or the double fault TSS.
+pub unsafe fn init() {
+    let idt = &mut *addr_of_mut!(IDT);
+    let stubs = &*addr_of!(isr_stub_table);
+
+    for (vector, &stub) in stubs.iter().enumerate() {
+        idt[vector] = Gate::new(stub, KERNEL_CODE_SELECTOR, GateType::Interrupt, 0);
+    }
+    tss::init_double_fault_task(double_fault_entry as *const () as u32);
+    idt[DOUBLE_FAULT_VECTOR] = Gate::new(0, DOUBLE_FAULT_TSS_SELECTOR, GateType::Task, 0);
+
+    load();
+}
+
+/// Loads `IDT` into the IDTR.
+unsafe fn load() {
+    let pointer = DescriptorTablePointer {
+        limit: (size_of::<[Gate; IDT_ENTRIES]>() - 1) as u16,
+        base: addr_of!(IDT) as usize,
+    };
+
+    asm!("lidt [{}]", in(reg) &pointer, options(readonly, nostack, preserves_flags));
+}
+
+/// Sets the interrupt flag so the CPU starts accepting maskable interrupts.
+pub fn enable_interrupts() {
+    unsafe { asm!("sti", options(nomem, nostack)) };
+}
+
+/// Clears the interrupt flag so the CPU ignores maskable interrupts.
+pub fn disable_interrupts() {
+    unsafe { asm!("cli", options(nomem, nostack)) };
+}
+
+/// Runs `f` with interrupts disabled, then restores the interrupt flag to what it was before.
+///
+/// Used for read-modify-write sequences an interrupt handler could interleave with, like updating a PIC mask,
+/// and to read state that cannot be accessed atomically, like a `u64` on i386.
+pub fn without_interrupts<T>(f: impl FnOnce() -> T) -> T {
+    let flags: usize;
+    unsafe { asm!("pushf", "pop {}", "cli", out(reg) flags, options(nomem)) };
+
+    let res = f();
+
+    if flags & INTERRUPT_FLAG != 0 {
+        enable_interrupts();
+    }
+    res
+}
+
+/// Enables interrupts and halts until the next one arrives.
+///
+/// `sti` only takes effect after the following instruction, so no interrupt can slip in between
+/// the two. Calling this with interrupts disabled after checking for pending work therefore never
+/// misses the wakeup for work queued after the check.
+pub fn enable_interrupts_and_halt() {
+    unsafe { asm!("sti", "hlt", options(nomem, nostack)) };
+}
+
+/// Called by `isr_common` with a pointer to the saved registers of the interrupted code.
+#[no_mangle]
+extern "C" fn interrupt_dispatch(frame: &mut InterruptFrame) {
+    match frame.vector as usize {
+        PAGE_FAULT_VECTOR => exceptions::page_fault(frame),
+        vector if vector < EXCEPTION_VECTORS => exceptions::handle(frame),
+        vector if vector < STUB_VECTORS => irq::handle(frame),
+        _ => {}
+    }
+}
+
+// Entry stubs. Each one normalizes the stack to an `InterruptFrame` (pushing a dummy error code
+// where the CPU does not) and jumps to `isr_common`, which hands the frame to `interrupt_dispatch`.
+#[cfg(target_arch = "x86")]
+core::arch::global_asm!(
+    ".macro ISR_NO_ERROR_CODE vector",
+    "isr_stub_\\vector:",
+    "    push 0",
+    "    push \\vector",
+    "    jmp isr_common",
+    ".endm",
+    ".macro ISR_ERROR_CODE vector",
+    "isr_stub_\\vector:",
+    "    push \\vector",
+    "    jmp isr_common",
+    ".endm",
+    "",
+    ".section .text",
+    ".irp vector, 0, 1, 2, 3, 4, 5, 6, 7, 9, 15, 16, 18, 19, 20, 22, 23, 24, 25, 26, 27, 28, 31",
+    "    ISR_NO_ERROR_CODE \\vector",
+    ".endr",
+    ".irp vector, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47",
+    "    ISR_NO_ERROR_CODE \\vector",
+    ".endr",
+    ".irp vector, 8, 10, 11, 12, 13, 14, 17, 21, 29, 30",
+    "    ISR_ERROR_CODE \\vector",
+    ".endr",
+    "",
+    "isr_common:",
+    "    pushad",
+    "    cld",
+    "    push esp",
+    "    call interrupt_dispatch",
+    "    add esp, 4",
+    "    popad",
+    "    add esp, 8",
+    "    iretd",
+    "",
+    ".pushsection .rodata",
+    ".global isr_stub_table",
+    "isr_stub_table:",
+    ".irp vector, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31",
+    "    .long isr_stub_\\vector",
+    ".endr",
+    ".irp vector, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47",
+    "    .long isr_stub_\\vector",
+    ".endr",
+    ".popsection",
+);
+
+/// The kind of an IDT gate, stored in the lower nibble of its type attributes.
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+#[repr(u8)]
+pub enum GateType {
+    /// Switches to the TSS referenced by the gate's selector.
+    Task = 0x5,
+    /// 32-bit interrupt gate: clears `IF` on entry.
+    Interrupt = 0xE,
+    /// 32-bit trap gate: leaves `IF` untouched.
+    Trap = 0xF,
+}
+
+/// A single 8-byte IDT entry.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct Gate {
+    offset_low: u16,
+    selector: u16,
+    zero: u8,
+    type_attributes: u8,
+    offset_high: u16,
+}
+
+impl Gate {
+    const PRESENT: u8 = 1 << 7;
+    const RING_SHIFT: u8 = 5;
+
+    /// A non-present gate.
+    pub const fn missing() -> Self {
+        Gate {
+            offset_low: 0,
+            selector: 0,
+            zero: 0,
+            type_attributes: 0,
+            offset_high: 0,
+        }
+    }
+
+    /// Creates a present gate.
+    ///
+    /// ### Parameters:
+    /// - `offset`: The address of the handler. Ignored by the CPU for task gates.
+    /// - `selector`: The code segment of the handler, or the TSS selector for task gates.
+    /// - `kind`: Whether this is a task, interrupt or trap gate.
+    /// - `ring`: The highest privilege level allowed to raise this vector with `int`.
+    pub const fn new(offset: u32, selector: SegmentSelector, kind: GateType, ring: u8) -> Self {
+        Gate {
+            offset_low: (offset & 0xFFFF) as u16,
+            selector: selector.bits(),
+            zero: 0,
+            type_attributes: Self::PRESENT | (ring & 0b11) << Self::RING_SHIFT | kind as u8,
+            offset_high: (offset >> 16) as u16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod gate_test {
+    use super::*;
+
+    #[test]
+    fn test_interrupt_gate_encoding() {
+        let gate = Gate::new(0x0010_2345, KERNEL_CODE_SELECTOR, GateType::Interrupt, 0);
+        let bits: u64 = unsafe { core::mem::transmute(gate) };
+
+        assert_eq!(bits, 0x0010_8E00_0008_2345);
+    }
+
+    #[test]
+    fn test_ring_3_trap_gate_encoding() {
+        let gate = Gate::new(0xDEAD_BEEF, KERNEL_CODE_SELECTOR, GateType::Trap, 3);
+        let bits: u64 = unsafe { core::mem::transmute(gate) };
+
+        assert_eq!(bits, 0xDEAD_EF00_0008_BEEF);
+    }
+
+    #[test]
+    fn test_task_gate_encoding() {
+        let gate = Gate::new(0, DOUBLE_FAULT_TSS_SELECTOR, GateType::Task, 0);
+        let bits: u64 = unsafe { core::mem::transmute(gate) };
+
+        assert_eq!(bits, 0x0000_8500_0030_0000);
+    }
+
+    #[test]
+    fn test_frame_matches_stub_layout() {
+        assert_eq!(size_of::<InterruptFrame>(), 13 * 4);
+    }
+
+    #[test]
+    fn test_interrupted_esp_skips_the_pushed_frame() {
+        let mut frame: InterruptFrame = unsafe { core::mem::zeroed() };
+        frame.esp = 0x1000;
+
+        // vector, error code, eip, cs and eflags.
+        assert_eq!(frame.interrupted_esp(), 0x1000 + 5 * 4);
+    }
+}
@@ -1,24 +1,192 @@
 #![no_std]
+#![cfg_attr(not(test), feature(alloc_error_handler))]
 
+extern crate alloc;
+
+use cmdline::{BootOptions, Cmdline, LogLevel};
 use print::{slice_to_str, u64_to_base};
+use terminal::{mouse::Mouse, ps2::controller::PortStatus, vga::Color};
 
+mod cmdline;
 mod gdt;
+mod idt;
+mod memory;
+mod multiboot;
 mod panic;
+mod pic;
+mod pit;
+mod port;
 mod print;
+mod ring_buffer;
+mod rtc;
+mod stack;
 mod terminal;
+mod tsc;
 
+/// Entry point called by `_start` in `assets/boot.s`.
+///
+/// ### Parameters:
+/// - `magic`: The value of `EAX` set by the boot loader, `multiboot::BOOTLOADER_MAGIC` for a Multiboot one.
+/// - `info`: The value of `EBX`, the physical address of the Multiboot information structure.
 #[no_mangle]
-pub extern "C" fn kernel_main() {
+pub extern "C" fn kernel_main(magic: u32, info: u32) {
+    unsafe {
+        stack::init_canary();
+        gdt::load();
+        idt::init();
+        pic::remap();
+        pit::init(pit::DEFAULT_FREQUENCY);
+    }
+
+    let boot_info = unsafe { multiboot::BootInfo::new(magic, info) };
+    let cmdline = Cmdline::new(boot_info.as_ref().ok().and_then(|b| b.cmdline()).unwrap_or(""));
+
     let mut t = terminal::Terminal::default();
+    let options = BootOptions::parse(&cmdline, |arg| {
+        t.write_color_str("ignoring invalid option ", Color::Error as u8);
+        t.write_color_str(arg.key, Color::Error as u8);
+        if let Some(value) = arg.value {
+            t.write_color_str("=", Color::Error as u8);
+            t.write_color_str(value, Color::Error as u8);
+        }
+        t.write_str("\n");
+    });
+    t.set_color(options.color);
+    t.set_screen_count(options.screens);
+
     let (slice, len) = u64_to_base(42_u64, 10).unwrap();
     let string = slice_to_str((&slice, len)).unwrap();
     t.write_str(string);
     t.write_str("\n");
+
+    match boot_info {
+        Ok(boot_info) => {
+            if options.log_level >= LogLevel::Info {
+                boot_info.write_to(&mut t, options.log_level >= LogLevel::Debug);
+            }
+            let stats = memory::frame::init(&boot_info);
+            if options.log_level >= LogLevel::Info {
+                write_frame_stats(&mut t, stats);
+            }
+            // Drops the identity mapping of `_start`, which `boot_info` is read through.
+            if unsafe { memory::paging::init(boot_info) }.is_err() {
+                t.write_color_str("paging: no memory for the page directory\n", Color::Error as u8);
+            } else {
+                let stats = unsafe { memory::heap::init() };
+                if stats.size == 0 {
+                    t.write_color_str("heap: no memory\n", Color::Error as u8);
+                } else if options.log_level >= LogLevel::Debug {
+                    let (slice, len) = u64_to_base(stats.size as u64 / 1024, 10).unwrap();
+                    t.write_str("heap: ");
+                    t.write_str(slice_to_str((&slice, len)).unwrap());
+                    t.write_str(" KiB\n");
+                }
+            }
+        }
+        Err(e) => e.write_to(&mut t),
+    }
+    if options.serial && options.log_level >= LogLevel::Warn {
+        t.write_color_str("serial console is not supported yet\n", Color::Error as u8);
+    }
+
+    let tsc = tsc::calibrate();
+    if options.log_level >= LogLevel::Info {
+        let (slice, len) = u64_to_base(pit::frequency() as u64, 10).unwrap();
+        t.write_str("timer: ");
+        t.write_str(slice_to_str((&slice, len)).unwrap());
+        t.write_str(" Hz\n");
+
+        t.write_str("tsc: ");
+        match tsc {
+            Ok(khz) => {
+                let (slice, len) = u64_to_base(khz as u64 / 1000, 10).unwrap();
+                t.write_str(slice_to_str((&slice, len)).unwrap());
+                t.write_str(" MHz\n");
+            }
+            Err(_) => t.write_color_str("not available\n", Color::Error as u8),
+        }
+
+        t.write_str("time: ");
+        rtc::now().write_to(&mut t);
+        t.write_str("\n");
+    }
+    if options.log_level >= LogLevel::Debug {
+        let (slice, len) = u64_to_base(stack::size() as u64 / 1024, 10).unwrap();
+        t.write_str("stack: ");
+        t.write_str(slice_to_str((&slice, len)).unwrap());
+        t.write_str(" KiB\n");
+    }
+
+    let mut mouse = None;
+    match unsafe { terminal::ps2::controller::init() } {
+        Ok(report) => {
+            if options.log_level >= LogLevel::Info {
+                report.write_to(&mut t);
+            }
+            if report.second_port == PortStatus::Working {
+                mouse = init_mouse(&mut t);
+            }
+        }
+        Err(_) => t.write_color_str("PS/2 controller is not responding\n", Color::Error as u8),
+    }
     t.flush();
+
+    unsafe { terminal::ps2::enable_interrupts() };
+    if mouse.is_some() {
+        unsafe { terminal::ps2::enable_mouse_interrupts() };
+    }
+    idt::enable_interrupts();
+
+    let mut keyboard = terminal::keyboard::Keyboard::new();
+    keyboard.set_layout(options.layout);
+    if keyboard.sync_leds().is_err() {
+        t.write_color_str("keyboard LEDs are not responding\n", Color::Error as u8);
+        t.flush();
+    }
+
     loop {
-        if let Some(key) = terminal::ps2::read_if_ready() {
-            t.handle_key(key);
-            t.flush();
+        stack::check();
+        terminal::ps2::wait_for_input();
+        while let Some(event) = keyboard.read_buffered() {
+            t.handle_key(event);
+        }
+        if let Some(mouse) = mouse.as_mut() {
+            while let Some(event) = mouse.read_buffered() {
+                t.handle_mouse(event);
+            }
+        }
+        t.flush();
+    }
+}
+
+/// Writes how much physical memory the frame allocator manages and how much of it is free.
+fn write_frame_stats(t: &mut terminal::Terminal, stats: memory::frame::FrameStats) {
+    const KIB_PER_FRAME: u64 = (memory::frame::FRAME_SIZE / 1024) as u64;
+
+    let (slice, len) = u64_to_base(stats.free as u64 * KIB_PER_FRAME, 10).unwrap();
+    t.write_str("frames: ");
+    t.write_str(slice_to_str((&slice, len)).unwrap());
+    let (slice, len) = u64_to_base(stats.total as u64 * KIB_PER_FRAME, 10).unwrap();
+    t.write_str(" KiB free of ");
+    t.write_str(slice_to_str((&slice, len)).unwrap());
+    t.write_str(" KiB\n");
+}
+
+/// Sets up the mouse on the second PS/2 port and reports whether it has a scroll wheel.
+fn init_mouse(t: &mut terminal::Terminal) -> Option<Mouse> {
+    t.write_str("mouse: ");
+    match unsafe { terminal::mouse::init() } {
+        Ok(mouse) => {
+            if mouse.has_wheel() {
+                t.write_str("ready, with scroll wheel\n");
+            } else {
+                t.write_str("ready\n");
+            }
+            Some(mouse)
+        }
+        Err(_) => {
+            t.write_color_str("not responding\n", Color::Error as u8);
+            None
         }
     }
 }
@@ -0,0 +1,376 @@
+use core::ptr::addr_of;
+
+use spin::Mutex;
+
+use crate::multiboot::{BootInfo, MemoryKind};
+
+/// Size of a physical frame, and of a page.
+pub const FRAME_SIZE: usize = 4096;
+
+/// Number of frames in the 32-bit physical address space, memory above 4 GiB is ignored.
+const MAX_FRAMES: usize = 1 << 20;
+const BITS_PER_WORD: usize = u32::BITS as usize;
+const BITMAP_WORDS: usize = MAX_FRAMES / BITS_PER_WORD;
+
+/// End of the first megabyte, which holds the real mode IVT, the BIOS data area, the VGA buffer and ROMs.
+const LOW_MEMORY_END: usize = 0x10_0000;
+
+extern "C" {
+    /// Physical address of the first byte of the kernel image, defined in `assets/linker.ld`.
+    static kernel_start: u8;
+    /// Physical address of the first byte after the kernel image, page aligned.
+    static kernel_end: u8;
+}
+
+/// The allocator used by the rest of the kernel, filled by `init`.
+pub static FRAME_ALLOCATOR: Mutex<FrameAllocator> = Mutex::new(FrameAllocator::new());
+
+/// A 4 KiB aligned block of physical memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Frame {
+    number: usize,
+}
+
+#[allow(dead_code)]
+impl Frame {
+    /// Returns the frame `address` belongs to.
+    pub const fn containing(address: usize) -> Self {
+        Frame { number: address / FRAME_SIZE }
+    }
+
+    pub const fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Returns the physical address of the first byte of the frame.
+    pub const fn start_address(&self) -> usize {
+        self.number * FRAME_SIZE
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// The frame is free already, reserved, or was never usable memory.
+    NotAllocated(Frame),
+}
+
+/// Usage of the physical memory, in frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames the memory map marks as available.
+    pub total: usize,
+    /// Available frames kept for the kernel image, the first megabyte, the modules and the boot information.
+    pub reserved: usize,
+    /// Frames handed out by `allocate` and `allocate_contiguous`.
+    pub allocated: usize,
+    pub free: usize,
+}
+
+/// Bitmap allocator over the physical frames, one bit per frame, set when the frame is free.
+///
+/// Frames start as used, `add_region` frees the available ones and `reserve` takes back those
+/// that hold something. A second bitmap remembers which used frames were handed out, so that only
+/// those can be freed. Both cover 4 GiB and start zeroed, so the 256 KiB they take together live in
+/// `.bss` rather than in the kernel image.
+pub struct FrameAllocator {
+    free_bitmap: [u32; BITMAP_WORDS],
+    /// Frames returned by `allocate` and `allocate_contiguous` and not freed yet.
+    allocated_bitmap: [u32; BITMAP_WORDS],
+    /// Number of bitmap words to search, up to the last available frame.
+    words: usize,
+    /// Word to start the next single frame search from, no word before it has a free bit.
+    next_word: usize,
+    total: usize,
+    reserved: usize,
+    allocated: usize,
+}
+
+impl FrameAllocator {
+    pub const fn new() -> Self {
+        FrameAllocator {
+            free_bitmap: [0; BITMAP_WORDS],
+            allocated_bitmap: [0; BITMAP_WORDS],
+            words: 0,
+            next_word: 0,
+            total: 0,
+            reserved: 0,
+            allocated: 0,
+        }
+    }
+
+    fn is_free(&self, number: usize) -> bool {
+        test_bit(&self.free_bitmap, number)
+    }
+
+    fn set_free(&mut self, number: usize, free: bool) {
+        set_bit(&mut self.free_bitmap, number, free);
+    }
+
+    fn is_allocated(&self, number: usize) -> bool {
+        test_bit(&self.allocated_bitmap, number)
+    }
+
+    /// Marks the frame as used and handed out, or as free.
+    fn set_allocated(&mut self, number: usize, allocated: bool) {
+        set_bit(&mut self.free_bitmap, number, !allocated);
+        set_bit(&mut self.allocated_bitmap, number, allocated);
+    }
+
+    /// Frees the frames entirely inside `[base, end)`, a region the memory map marks as available.
+    ///
+    /// The frame numbers are computed in `u64`: `end` is 4 GiB for a region reaching the top of the
+    /// 32-bit address space, which does not fit in a `usize` on i386.
+    pub fn add_region(&mut self, base: u64, end: u64) {
+        let first = base.div_ceil(FRAME_SIZE as u64).min(MAX_FRAMES as u64) as usize;
+        let last = (end / FRAME_SIZE as u64).min(MAX_FRAMES as u64) as usize;
+
+        for number in first..last {
+            if !self.is_free(number) {
+                self.set_free(number, true);
+                self.total += 1;
+            }
+        }
+
+        self.words = self.words.max(last.div_ceil(BITS_PER_WORD));
+        self.next_word = 0;
+    }
+
+    /// Marks the frames overlapping `[start, end)` as used so they are never allocated.
+    pub fn reserve(&mut self, start: usize, end: usize) {
+        let first = start / FRAME_SIZE;
+        let last = end.div_ceil(FRAME_SIZE).min(MAX_FRAMES);
+
+        for number in first..last {
+            if self.is_free(number) {
+                self.set_free(number, false);
+                self.reserved += 1;
+            }
+        }
+    }
+
+    /// Returns a free frame, the lowest one.
+    #[allow(dead_code)]
+    pub fn allocate(&mut self) -> Option<Frame> {
+        let word = (self.next_word..self.words).find(|&word| self.free_bitmap[word] != 0)?;
+        let number = word * BITS_PER_WORD + self.free_bitmap[word].trailing_zeros() as usize;
+
+        self.next_word = word;
+        self.set_allocated(number, true);
+        self.allocated += 1;
+        Some(Frame { number })
+    }
+
+    /// Returns the first of `count` free frames that follow each other, for buffers that must be
+    /// physically contiguous such as DMA ones.
+    #[allow(dead_code)]
+    pub fn allocate_contiguous(&mut self, count: usize) -> Option<Frame> {
+        if count == 0 {
+            return None;
+        }
+
+        let mut run_start = 0;
+        let mut run = 0;
+        for number in self.next_word * BITS_PER_WORD..self.words * BITS_PER_WORD {
+            if !self.is_free(number) {
+                run = 0;
+                continue;
+            }
+            if run == 0 {
+                run_start = number;
+            }
+            run += 1;
+
+            if run == count {
+                for number in run_start..run_start + count {
+                    self.set_allocated(number, true);
+                }
+                self.allocated += count;
+                return Some(Frame { number: run_start });
+            }
+        }
+
+        None
+    }
+
+    /// Gives back a frame returned by `allocate`.
+    #[allow(dead_code)]
+    pub fn free(&mut self, frame: Frame) -> Result<(), FrameError> {
+        self.free_contiguous(frame, 1)
+    }
+
+    /// Gives back `count` frames starting at `frame`, returned by `allocate_contiguous`.
+    ///
+    /// ### Returns:
+    /// - `Err(FrameError::NotAllocated)` with the first frame that was not handed out, e.g. a free or
+    ///   reserved one, nothing is freed then.
+    #[allow(dead_code)]
+    pub fn free_contiguous(&mut self, frame: Frame, count: usize) -> Result<(), FrameError> {
+        let frames = frame.number..frame.number.saturating_add(count);
+
+        if let Some(number) = frames
+            .clone()
+            .find(|&number| number >= self.words * BITS_PER_WORD || !self.is_allocated(number))
+        {
+            return Err(FrameError::NotAllocated(Frame { number }));
+        }
+
+        for number in frames {
+            self.set_allocated(number, false);
+        }
+        self.allocated = self.allocated.saturating_sub(count);
+        self.next_word = self.next_word.min(frame.number / BITS_PER_WORD);
+        Ok(())
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            total: self.total,
+            reserved: self.reserved,
+            allocated: self.allocated,
+            free: self.total - self.reserved - self.allocated,
+        }
+    }
+}
+
+fn test_bit(bitmap: &[u32; BITMAP_WORDS], number: usize) -> bool {
+    bitmap[number / BITS_PER_WORD] & (1 << (number % BITS_PER_WORD)) != 0
+}
+
+fn set_bit(bitmap: &mut [u32; BITMAP_WORDS], number: usize, value: bool) {
+    let bit = 1 << (number % BITS_PER_WORD);
+
+    if value {
+        bitmap[number / BITS_PER_WORD] |= bit;
+    } else {
+        bitmap[number / BITS_PER_WORD] &= !bit;
+    }
+}
+
+/// Fills `FRAME_ALLOCATOR` from the memory map, then reserves the first megabyte, the kernel image,
+/// the modules and the boot information.
+///
+/// ### Returns:
+/// - The usage of the physical memory once initialized.
+pub fn init(boot_info: &BootInfo) -> FrameStats {
+    let mut allocator = FRAME_ALLOCATOR.lock();
+
+    if let Some(map) = boot_info.memory_map() {
+        for region in map.filter(|region| region.kind == MemoryKind::Available) {
+            allocator.add_region(region.base, region.end());
+        }
+    }
+
+    allocator.reserve(0, LOW_MEMORY_END);
+    allocator.reserve(addr_of!(kernel_start) as usize, addr_of!(kernel_end) as usize);
+    if let Some(modules) = boot_info.modules() {
+        for module in modules {
+            allocator.reserve(module.start as usize, module.end as usize);
+        }
+    }
+    let (start, end) = boot_info.footprint();
+    allocator.reserve(start, end);
+
+    allocator.stats()
+}
+
+#[cfg(test)]
+mod frame_test {
+    use super::*;
+
+    /// 640 KiB of low memory and 1 MiB from 0x100000, as a tiny machine would report.
+    fn allocator() -> FrameAllocator {
+        let mut allocator = FrameAllocator::new();
+        allocator.add_region(0, 0x9FC00);
+        allocator.add_region(0x100000, 0x200000);
+        allocator
+    }
+
+    #[test]
+    fn test_regions_only_count_whole_frames() {
+        let mut allocator = allocator();
+        assert_eq!(allocator.stats().total, 159 + 256);
+
+        allocator.reserve(0, LOW_MEMORY_END);
+        allocator.reserve(0x100800, 0x101001);
+        assert_eq!(
+            allocator.stats(),
+            FrameStats {
+                total: 415,
+                reserved: 159 + 2,
+                allocated: 0,
+                free: 254,
+            }
+        );
+    }
+
+    #[test]
+    fn test_allocate_and_free() {
+        let mut allocator = allocator();
+        allocator.reserve(0, LOW_MEMORY_END);
+
+        let first = allocator.allocate().unwrap();
+        let second = allocator.allocate().unwrap();
+        assert_eq!(first.start_address(), 0x100000);
+        assert_eq!(second.start_address(), 0x101000);
+
+        assert_eq!(allocator.free(first), Ok(()));
+        assert_eq!(allocator.free(first), Err(FrameError::NotAllocated(first)));
+        assert_eq!(allocator.allocate(), Some(first));
+        assert_eq!(allocator.stats().allocated, 2);
+    }
+
+    #[test]
+    fn test_allocate_until_out_of_memory() {
+        let mut allocator = FrameAllocator::new();
+        allocator.add_region(0x100000, 0x100000 + 3 * FRAME_SIZE as u64);
+
+        assert!(allocator.allocate().is_some());
+        assert!(allocator.allocate().is_some());
+        assert!(allocator.allocate().is_some());
+        assert_eq!(allocator.allocate(), None);
+        assert_eq!(allocator.stats().free, 0);
+    }
+
+    #[test]
+    fn test_allocate_contiguous_skips_holes() {
+        let mut allocator = allocator();
+        allocator.reserve(0, 0x9C000);
+
+        // Frames 0x9C to 0x9E are free but too few, the run has to start after the hole.
+        let frames = allocator.allocate_contiguous(4).unwrap();
+        assert_eq!(frames, Frame::containing(0x100000));
+        assert_eq!(allocator.allocate(), Some(Frame::containing(0x9C000)));
+
+        assert_eq!(allocator.free_contiguous(frames, 4), Ok(()));
+        assert_eq!(allocator.stats().allocated, 1);
+        assert_eq!(allocator.allocate_contiguous(0), None);
+        assert_eq!(allocator.allocate_contiguous(MAX_FRAMES), None);
+    }
+
+    #[test]
+    fn test_region_up_to_4_gib() {
+        let mut allocator = FrameAllocator::new();
+        allocator.add_region(0xFFFF_0000, 1 << 32);
+        assert_eq!(allocator.stats().total, 16);
+
+        // Memory above 4 GiB is out of reach without PAE.
+        allocator.add_region(1 << 32, 1 << 33);
+        assert_eq!(allocator.stats().total, 16);
+        assert_eq!(allocator.allocate(), Some(Frame::containing(0xFFFF_0000)));
+    }
+
+    #[test]
+    fn test_free_rejects_reserved_frames() {
+        let mut allocator = allocator();
+        allocator.reserve(0, LOW_MEMORY_END);
+        let before = allocator.stats();
+
+        let reserved = Frame::containing(0x1000);
+        assert_eq!(allocator.free(reserved), Err(FrameError::NotAllocated(reserved)));
+        // 0xA0000 is in the hole between the two regions, it was never available.
+        let hole = Frame::containing(0xA0000);
+        assert_eq!(allocator.free_contiguous(hole, 2), Err(FrameError::NotAllocated(hole)));
+        assert_eq!(allocator.stats(), before);
+    }
+}
@@ -0,0 +1,378 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem::{align_of, size_of},
+    ptr::{null_mut, NonNull},
+};
+
+use spin::Mutex;
+
+use super::{
+    frame::FRAME_ALLOCATOR,
+    paging::{self, PageFlags, PAGE_SIZE},
+};
+use crate::idt;
+
+/// Virtual address of the first byte of the heap, above the 8 MiB the kernel image can take.
+pub const HEAP_START: usize = 0xD000_0000;
+/// The heap never grows past this address.
+pub const HEAP_END: usize = 0xE000_0000;
+/// Size mapped by `init`, the heap then grows by at least `HEAP_GROWTH` when it runs out.
+const HEAP_INITIAL_SIZE: usize = 256 * 1024;
+const HEAP_GROWTH: usize = 64 * 1024;
+
+/// The allocator behind `alloc::boxed::Box`, `alloc::vec::Vec` and friends.
+#[cfg_attr(not(test), global_allocator)]
+pub static KERNEL_HEAP: KernelHeap = KernelHeap::new();
+
+/// Header written at the start of every free block, the free blocks form a list sorted by address.
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// Smallest block the heap hands out or keeps track of, so that every freed block can hold its header.
+const MIN_BLOCK_SIZE: usize = size_of::<FreeBlock>();
+
+/// Usage of the heap, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Bytes given to the heap with `add_region`.
+    pub size: usize,
+    pub used: usize,
+    pub free: usize,
+    /// Size of the largest free block, the largest allocation that can succeed without growing.
+    pub largest_free_block: usize,
+}
+
+/// A first fit allocator over a linked list of free blocks.
+///
+/// Blocks are kept sorted by address so a freed block is merged with its free neighbours,
+/// which keeps the heap from splitting into blocks too small to be useful.
+pub struct Heap {
+    head: *mut FreeBlock,
+    size: usize,
+    used: usize,
+}
+
+// SAFETY: The free blocks are only reached through `head`, so moving the `Heap` moves their ownership.
+unsafe impl Send for Heap {}
+
+impl Heap {
+    pub const fn empty() -> Self {
+        Heap {
+            head: null_mut(),
+            size: 0,
+            used: 0,
+        }
+    }
+
+    /// Hands `[start, start + size)` to the heap.
+    ///
+    /// ## SAFETY
+    /// The memory must be mapped, writable, unused by anything else and live as long as the heap.
+    pub unsafe fn add_region(&mut self, start: usize, size: usize) {
+        let aligned = start.next_multiple_of(align_of::<FreeBlock>());
+        let size = size.saturating_sub(aligned - start) / MIN_BLOCK_SIZE * MIN_BLOCK_SIZE;
+        if size < MIN_BLOCK_SIZE {
+            return;
+        }
+
+        self.insert(aligned, size);
+        self.size += size;
+    }
+
+    /// Returns the size and alignment actually reserved for `layout`, so that the block can hold a
+    /// `FreeBlock` once freed and the blocks around it stay aligned.
+    fn block_layout(layout: Layout) -> (usize, usize) {
+        let size = layout.size().max(MIN_BLOCK_SIZE).next_multiple_of(MIN_BLOCK_SIZE);
+        let align = layout.align().max(align_of::<FreeBlock>());
+
+        (size, align)
+    }
+
+    /// Returns where an allocation of `size` bytes aligned on `align` starts in the free block
+    /// `[start, end)`, if it fits. The space left before and after it must be empty or big enough
+    /// to stay a free block.
+    fn fit(start: usize, end: usize, size: usize, align: usize) -> Option<usize> {
+        let mut alloc_start = start.next_multiple_of(align);
+        if alloc_start != start && alloc_start - start < MIN_BLOCK_SIZE {
+            alloc_start = (start + MIN_BLOCK_SIZE).next_multiple_of(align);
+        }
+
+        let alloc_end = alloc_start.checked_add(size)?;
+        if alloc_end > end {
+            return None;
+        }
+
+        let rest = end - alloc_end;
+        if rest != 0 && rest < MIN_BLOCK_SIZE {
+            return None;
+        }
+        Some(alloc_start)
+    }
+
+    /// Returns a block for `layout` taken from the first free block it fits in.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let (size, align) = Self::block_layout(layout);
+        let mut link: *mut *mut FreeBlock = &mut self.head;
+
+        unsafe {
+            while !(*link).is_null() {
+                let block = *link;
+                let start = block as usize;
+                let end = start + (*block).size;
+
+                if let Some(alloc_start) = Self::fit(start, end, size, align) {
+                    let alloc_end = alloc_start + size;
+                    let mut rest = (*block).next;
+                    if alloc_end < end {
+                        let tail = alloc_end as *mut FreeBlock;
+                        tail.write(FreeBlock {
+                            size: end - alloc_end,
+                            next: rest,
+                        });
+                        rest = tail;
+                    }
+
+                    if alloc_start > start {
+                        (*block).size = alloc_start - start;
+                        (*block).next = rest;
+                    } else {
+                        *link = rest;
+                    }
+
+                    self.used += size;
+                    return NonNull::new(alloc_start as *mut u8);
+                }
+
+                link = &mut (*block).next;
+            }
+        }
+
+        None
+    }
+
+    /// Gives back a block returned by `allocate`.
+    ///
+    /// ## SAFETY
+    /// `ptr` must have been returned by `allocate` on this heap with the same `layout`, and not be freed already.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let (size, _) = Self::block_layout(layout);
+
+        self.insert(ptr.as_ptr() as usize, size);
+        self.used -= size;
+    }
+
+    /// Inserts the free block `[start, start + size)` in the list, merged with its neighbours if they touch it.
+    unsafe fn insert(&mut self, start: usize, size: usize) {
+        let mut previous: *mut FreeBlock = null_mut();
+        let mut next = self.head;
+        while !next.is_null() && (next as usize) < start {
+            previous = next;
+            next = (*next).next;
+        }
+
+        let block = start as *mut FreeBlock;
+        block.write(FreeBlock { size, next });
+        if !next.is_null() && start + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+
+        if previous.is_null() {
+            self.head = block;
+        } else if previous as usize + (*previous).size == start {
+            (*previous).size += (*block).size;
+            (*previous).next = (*block).next;
+        } else {
+            (*previous).next = block;
+        }
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        let mut largest_free_block = 0;
+        let mut block = self.head;
+        while !block.is_null() {
+            unsafe {
+                largest_free_block = largest_free_block.max((*block).size);
+                block = (*block).next;
+            }
+        }
+
+        HeapStats {
+            size: self.size,
+            used: self.used,
+            free: self.size - self.used,
+            largest_free_block,
+        }
+    }
+}
+
+/// The kernel heap: a `Heap` over pages mapped from `HEAP_START` on, growing towards `HEAP_END` on demand.
+pub struct KernelHeap {
+    heap: Mutex<Heap>,
+    /// End of the mapped part of the heap, 0 until `init` has run.
+    top: Mutex<usize>,
+}
+
+impl KernelHeap {
+    pub const fn new() -> Self {
+        KernelHeap {
+            heap: Mutex::new(Heap::empty()),
+            top: Mutex::new(0),
+        }
+    }
+
+    /// Maps at least `size` more bytes at the top of the heap and hands them to it.
+    ///
+    /// ### Returns:
+    /// - `false` if the heap is not initialized, would pass `HEAP_END` or there are no free frames left.
+    ///   The pages mapped before running out are kept.
+    fn grow(&self, heap: &mut Heap, size: usize) -> bool {
+        let mut top = self.top.lock();
+        if *top == 0 {
+            return false;
+        }
+
+        let size = size.next_multiple_of(PAGE_SIZE);
+        let start = *top;
+        if size > HEAP_END - start {
+            return false;
+        }
+
+        for page in (start..start + size).step_by(PAGE_SIZE) {
+            // `paging::map` locks `FRAME_ALLOCATOR` again when it needs a page table, so the guard must be gone by then.
+            let frame = FRAME_ALLOCATOR.lock().allocate();
+            let mapped = frame.is_some_and(|frame| {
+                let mapped = unsafe { paging::map(page, frame, PageFlags::kernel()) }.is_ok();
+                if !mapped {
+                    let _ = FRAME_ALLOCATOR.lock().free(frame);
+                }
+                mapped
+            });
+            if !mapped {
+                unsafe { heap.add_region(start, page - start) };
+                *top = page;
+                return false;
+            }
+        }
+
+        unsafe { heap.add_region(start, size) };
+        *top = start + size;
+        true
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        idt::without_interrupts(|| self.heap.lock().stats())
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        idt::without_interrupts(|| {
+            let mut heap = self.heap.lock();
+            if let Some(ptr) = heap.allocate(layout) {
+                return ptr.as_ptr();
+            }
+
+            // Enough for the block even if its alignment wastes almost `align` bytes in front of it.
+            let needed = layout.size() + layout.align() + MIN_BLOCK_SIZE;
+            if !self.grow(&mut heap, needed.max(HEAP_GROWTH)) {
+                return null_mut();
+            }
+            heap.allocate(layout).map_or(null_mut(), |ptr| ptr.as_ptr())
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            idt::without_interrupts(|| self.heap.lock().deallocate(ptr, layout));
+        }
+    }
+}
+
+/// Maps the first `HEAP_INITIAL_SIZE` bytes of the heap, allocations fail until then.
+///
+/// ### Returns:
+/// - The usage of the heap, its size is 0 if no frames were available.
+///
+/// ## SAFETY
+/// `paging::init` must have succeeded.
+pub unsafe fn init() -> HeapStats {
+    idt::without_interrupts(|| {
+        *KERNEL_HEAP.top.lock() = HEAP_START;
+        KERNEL_HEAP.grow(&mut KERNEL_HEAP.heap.lock(), HEAP_INITIAL_SIZE);
+    });
+
+    KERNEL_HEAP.stats()
+}
+
+#[cfg(test)]
+mod heap_test {
+    use super::*;
+
+    #[repr(C, align(16))]
+    struct Memory([u8; 1024]);
+
+    fn heap(memory: &mut Memory) -> Heap {
+        let mut heap = Heap::empty();
+        unsafe { heap.add_region(memory.0.as_mut_ptr() as usize, memory.0.len()) };
+        heap
+    }
+
+    #[test]
+    fn test_allocate_and_merge_on_free() {
+        let mut memory = Memory([0; 1024]);
+        let mut heap = heap(&mut memory);
+        let layout = Layout::from_size_align(100, 4).unwrap();
+
+        let (block, _) = Heap::block_layout(layout);
+
+        let a = heap.allocate(layout).unwrap();
+        let b = heap.allocate(layout).unwrap();
+        let c = heap.allocate(layout).unwrap();
+        assert_eq!(b.as_ptr() as usize - a.as_ptr() as usize, block);
+        assert_eq!(heap.stats().used, 3 * block);
+
+        unsafe {
+            heap.deallocate(a, layout);
+            heap.deallocate(c, layout);
+            assert_eq!(heap.stats().largest_free_block, 1024 - 2 * block);
+            heap.deallocate(b, layout);
+        }
+        assert_eq!(
+            heap.stats(),
+            HeapStats {
+                size: 1024,
+                used: 0,
+                free: 1024,
+                largest_free_block: 1024,
+            }
+        );
+    }
+
+    #[test]
+    fn test_alignment_keeps_the_padding_free() {
+        let mut memory = Memory([0; 1024]);
+        let mut heap = heap(&mut memory);
+
+        let small = heap.allocate(Layout::from_size_align(8, 8).unwrap()).unwrap();
+        let aligned = heap.allocate(Layout::from_size_align(64, 256).unwrap()).unwrap();
+        assert_eq!(aligned.as_ptr() as usize % 256, 0);
+
+        // The padding in front of `aligned` went back to the list and serves the next small block.
+        let next = heap.allocate(Layout::from_size_align(16, 8).unwrap()).unwrap();
+        assert!((next.as_ptr() as usize) < aligned.as_ptr() as usize);
+        assert!(next.as_ptr() as usize > small.as_ptr() as usize);
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        let mut memory = Memory([0; 1024]);
+        let mut heap = heap(&mut memory);
+
+        assert!(heap.allocate(Layout::from_size_align(1025, 1).unwrap()).is_none());
+        assert!(heap.allocate(Layout::from_size_align(1024, 1).unwrap()).is_some());
+        assert!(heap.allocate(Layout::from_size_align(1, 1).unwrap()).is_none());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod frame;
+pub mod heap;
+pub mod paging;
+pub mod slab;
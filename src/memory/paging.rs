@@ -0,0 +1,338 @@
+use core::{
+    arch::asm,
+    ops::BitOr,
+    ptr::{addr_of, write_bytes},
+};
+
+use super::frame::{Frame, FRAME_ALLOCATOR, FRAME_SIZE};
+use crate::{gdt::tss, multiboot::BootInfo, stack};
+
+/// Virtual address of physical address 0, the kernel image is mapped at `kernel_start + KERNEL_OFFSET`.
+/// Must match `KERNEL_OFFSET` in `assets/boot.s` and `assets/linker.ld`.
+pub const KERNEL_OFFSET: usize = 0xC000_0000;
+
+pub const PAGE_SIZE: usize = FRAME_SIZE;
+
+/// Physical address of the VGA text buffer.
+const VGA_BUFFER_PHYSICAL: usize = 0xB8000;
+/// Virtual address the VGA text buffer is mapped at, by `_start` and then by `init`.
+pub const VGA_BUFFER: usize = KERNEL_OFFSET + VGA_BUFFER_PHYSICAL;
+
+/// Entries in a page directory or a page table.
+const ENTRIES: usize = 1024;
+
+/// The last directory entry points at the directory itself, so the page tables show up in the last
+/// 4 MiB of the address space and the directory in its last page.
+const RECURSIVE_INDEX: usize = ENTRIES - 1;
+const PAGE_TABLES: usize = RECURSIVE_INDEX << 22;
+const PAGE_DIRECTORY: usize = PAGE_TABLES + RECURSIVE_INDEX * PAGE_SIZE;
+
+extern "C" {
+    /// Physical bounds of the kernel image, see `frame::kernel_start`.
+    static kernel_start: u8;
+    static kernel_end: u8;
+}
+
+/// Builder for the flags of a page table entry.
+///
+/// See the [OSDev wiki](https://wiki.osdev.org/Paging#32-bit_Paging_(Protected_Mode_and_Real_Mode)) for the meaning of each bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageFlags(u32);
+
+#[allow(dead_code)]
+impl PageFlags {
+    const PRESENT: u32 = 1 << 0;
+    const WRITABLE: u32 = 1 << 1;
+    const USER: u32 = 1 << 2;
+    const WRITE_THROUGH: u32 = 1 << 3;
+    const CACHE_DISABLE: u32 = 1 << 4;
+    const MASK: u32 = 0xFFF;
+
+    /// A present, writable page only the kernel can access.
+    pub const fn kernel() -> Self {
+        PageFlags(Self::PRESENT | Self::WRITABLE)
+    }
+
+    /// Clears the writable bit. Only enforced for the kernel once `CR0.WP` is set.
+    pub const fn read_only(self) -> Self {
+        PageFlags(self.0 & !Self::WRITABLE)
+    }
+
+    /// Lets ring 3 access the page.
+    pub const fn user(self) -> Self {
+        PageFlags(self.0 | Self::USER)
+    }
+
+    /// Bypasses the caches, for memory mapped devices.
+    pub const fn cache_disabled(self) -> Self {
+        PageFlags(self.0 | Self::CACHE_DISABLE | Self::WRITE_THROUGH)
+    }
+
+    pub const fn is_present(self) -> bool {
+        self.0 & Self::PRESENT != 0
+    }
+
+    pub const fn is_writable(self) -> bool {
+        self.0 & Self::WRITABLE != 0
+    }
+
+    pub const fn is_user(self) -> bool {
+        self.0 & Self::USER != 0
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for PageFlags {
+    type Output = PageFlags;
+
+    fn bitor(self, rhs: PageFlags) -> PageFlags {
+        PageFlags(self.0 | rhs.0)
+    }
+}
+
+/// An entry of a page directory or a page table: the physical address of a frame and its flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+struct Entry(u32);
+
+impl Entry {
+    const UNUSED: Entry = Entry(0);
+
+    fn new(frame: Frame, flags: PageFlags) -> Self {
+        Entry(frame.start_address() as u32 | flags.bits() & PageFlags::MASK)
+    }
+
+    fn frame(self) -> Frame {
+        Frame::containing((self.0 & !PageFlags::MASK) as usize)
+    }
+
+    fn flags(self) -> PageFlags {
+        PageFlags(self.0 & PageFlags::MASK)
+    }
+
+    fn is_present(self) -> bool {
+        self.flags().is_present()
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagingError {
+    /// The page already maps a frame, `unmap` it first.
+    AlreadyMapped,
+    /// The page maps nothing.
+    NotMapped,
+    /// A new page table was needed and there are no free frames left.
+    OutOfFrames,
+}
+
+/// Index of the directory entry covering `address`, its upper 10 bits.
+const fn directory_index(address: usize) -> usize {
+    address >> 22 & (ENTRIES - 1)
+}
+
+/// Index of the page table entry covering `address`, its middle 10 bits.
+const fn table_index(address: usize) -> usize {
+    address >> 12 & (ENTRIES - 1)
+}
+
+/// Returns the directory entry covering `address`, through the recursive mapping.
+fn directory_entry(address: usize) -> *mut Entry {
+    (PAGE_DIRECTORY as *mut Entry).wrapping_add(directory_index(address))
+}
+
+/// Returns the page table covering `address`, through the recursive mapping.
+fn page_table(address: usize) -> *mut Entry {
+    (PAGE_TABLES + directory_index(address) * PAGE_SIZE) as *mut Entry
+}
+
+/// Builds the kernel page directory and switches to it. From then on the kernel image is only
+/// mapped in the higher half, next to the VGA buffer at `VGA_BUFFER` and the page tables.
+/// The guard page below the stack is left out so that an overflow faults.
+///
+/// ### Parameters:
+/// - `_boot_info`: Taken so that nothing read from the Multiboot information, which lives in physical
+///   memory, can be used once the identity mapping is gone.
+///
+/// ### Returns:
+/// - `Err(PagingError::OutOfFrames)` if the frame allocator has no memory, the boot mapping stays in use.
+///
+/// ## SAFETY
+/// Must run once, after `frame::init`, while the boot mapping of `_start` identity maps the frames
+/// handed out by `FRAME_ALLOCATOR`. The identity mapping is dropped: any pointer to physical memory
+/// outside of the kernel image is no longer valid afterwards.
+pub unsafe fn init(_boot_info: BootInfo) -> Result<(), PagingError> {
+    let mut frames = FRAME_ALLOCATOR.lock();
+    let directory_frame = frames.allocate().ok_or(PagingError::OutOfFrames)?;
+    let directory = directory_frame.start_address() as *mut Entry;
+    write_bytes(directory, 0, ENTRIES);
+
+    let mut map = |address: usize, frame: Frame, flags: PageFlags| -> Result<(), PagingError> {
+        let pde = directory.add(directory_index(address));
+        if !(*pde).is_present() {
+            let table = frames.allocate().ok_or(PagingError::OutOfFrames)?;
+            write_bytes(table.start_address() as *mut Entry, 0, ENTRIES);
+            *pde = Entry::new(table, PageFlags::kernel());
+        }
+
+        let table = (*pde).frame().start_address() as *mut Entry;
+        *table.add(table_index(address)) = Entry::new(frame, flags);
+        Ok(())
+    };
+
+    let start = addr_of!(kernel_start) as usize;
+    let end = addr_of!(kernel_end) as usize;
+    for physical in (start..end)
+        .step_by(PAGE_SIZE)
+        .filter(|&physical| !stack::is_guard_page(physical + KERNEL_OFFSET))
+    {
+        map(physical + KERNEL_OFFSET, Frame::containing(physical), PageFlags::kernel())?;
+    }
+    map(VGA_BUFFER, Frame::containing(VGA_BUFFER_PHYSICAL), PageFlags::kernel().cache_disabled())?;
+    *directory.add(RECURSIVE_INDEX) = Entry::new(directory_frame, PageFlags::kernel());
+
+    asm!("mov cr3, {}", in(reg) directory_frame.start_address(), options(nostack, preserves_flags));
+    tss::set_double_fault_page_directory(directory_frame.start_address() as u32);
+    Ok(())
+}
+
+/// Returns the physical address `address` is mapped to. The page tables are only reachable once `init` has run.
+#[allow(dead_code)]
+pub fn translate(address: usize) -> Option<usize> {
+    let pde = unsafe { *directory_entry(address) };
+    if !pde.is_present() {
+        return None;
+    }
+
+    let pte = unsafe { *page_table(address).add(table_index(address)) };
+    if !pte.is_present() {
+        return None;
+    }
+
+    Some(pte.frame().start_address() + (address & (PAGE_SIZE - 1)))
+}
+
+/// Returns the flags of the page containing `address`, `None` if it is not mapped.
+#[allow(dead_code)]
+pub fn flags(address: usize) -> Option<PageFlags> {
+    let pde = unsafe { *directory_entry(address) };
+    if !pde.is_present() {
+        return None;
+    }
+
+    let pte = unsafe { *page_table(address).add(table_index(address)) };
+    pte.is_present().then_some(pte.flags())
+}
+
+/// Maps the page containing `address` to `frame`, allocating its page table if needed.
+///
+/// ### Returns:
+/// - `Err(PagingError::AlreadyMapped)` if the page maps a frame already, the mapping is left as it was.
+/// - `Err(PagingError::OutOfFrames)` if a page table was needed and could not be allocated.
+///
+/// ## SAFETY
+/// `init` must have run. Mapping a frame that is also used elsewhere, e.g. by the kernel image, aliases it.
+#[allow(dead_code)]
+pub unsafe fn map(address: usize, frame: Frame, flags: PageFlags) -> Result<(), PagingError> {
+    let pde = directory_entry(address);
+    let table = page_table(address);
+
+    if !(*pde).is_present() {
+        let frame = FRAME_ALLOCATOR.lock().allocate().ok_or(PagingError::OutOfFrames)?;
+        // The directory entry decides the most permissive access to the 4 MiB, pages restrict it.
+        *pde = Entry::new(frame, PageFlags::kernel() | PageFlags(flags.bits() & PageFlags::USER));
+        invalidate(table as usize);
+        write_bytes(table, 0, ENTRIES);
+    } else if flags.is_user() && !(*pde).flags().is_user() {
+        *pde = Entry::new((*pde).frame(), (*pde).flags().user());
+        // Any page of the 4 MiB may be cached as supervisor only, invalidating them one by one costs more.
+        flush_tlb();
+    }
+
+    let pte = table.add(table_index(address));
+    if (*pte).is_present() {
+        return Err(PagingError::AlreadyMapped);
+    }
+
+    *pte = Entry::new(frame, flags);
+    invalidate(address);
+    Ok(())
+}
+
+/// Unmaps the page containing `address`. The page table is kept even if it becomes empty.
+///
+/// ### Returns:
+/// - `Ok(Frame)` with the frame the page mapped, which is not freed.
+/// - `Err(PagingError::NotMapped)` if the page maps nothing.
+///
+/// ## SAFETY
+/// `init` must have run, and nothing may use the page anymore.
+#[allow(dead_code)]
+pub unsafe fn unmap(address: usize) -> Result<Frame, PagingError> {
+    let pde = *directory_entry(address);
+    if !pde.is_present() {
+        return Err(PagingError::NotMapped);
+    }
+
+    let pte = page_table(address).add(table_index(address));
+    if !(*pte).is_present() {
+        return Err(PagingError::NotMapped);
+    }
+
+    let frame = (*pte).frame();
+    *pte = Entry::UNUSED;
+    invalidate(address);
+    Ok(frame)
+}
+
+/// Removes the translation of the page containing `address` from the TLB.
+fn invalidate(address: usize) {
+    unsafe { asm!("invlpg [{}]", in(reg) address, options(nostack, preserves_flags)) };
+}
+
+/// Removes every translation from the TLB by reloading `CR3`. No page is global, so none survives.
+fn flush_tlb() {
+    unsafe {
+        asm!(
+            "mov {0}, cr3",
+            "mov cr3, {0}",
+            out(reg) _,
+            options(nostack, preserves_flags)
+        )
+    };
+}
+
+#[cfg(test)]
+mod paging_test {
+    use super::*;
+
+    #[test]
+    fn test_address_split() {
+        assert_eq!(directory_index(0xC010_2345), 768);
+        assert_eq!(table_index(0xC010_2345), 0x102);
+        assert_eq!(directory_index(PAGE_DIRECTORY), RECURSIVE_INDEX);
+        assert_eq!(table_index(PAGE_DIRECTORY), RECURSIVE_INDEX);
+        assert_eq!(page_table(0xC010_2345) as usize, 0xFFF0_0000);
+    }
+
+    #[test]
+    fn test_flags() {
+        let flags = PageFlags::kernel().user().cache_disabled();
+        assert_eq!(flags.bits(), 0b11111);
+        assert!(flags.is_present() && flags.is_writable() && flags.is_user());
+        assert!(!PageFlags::kernel().read_only().is_writable());
+    }
+
+    #[test]
+    fn test_entry() {
+        let entry = Entry::new(Frame::containing(0x12_3456), PageFlags::kernel().read_only());
+
+        assert_eq!(entry.0, 0x12_3001);
+        assert_eq!(entry.frame(), Frame::containing(0x12_3000));
+        assert!(entry.is_present());
+        assert!(!Entry::UNUSED.is_present());
+    }
+}
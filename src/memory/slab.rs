@@ -0,0 +1,423 @@
+use core::{
+    alloc::Layout,
+    mem::size_of,
+    ptr::{null_mut, NonNull},
+};
+
+use alloc::alloc::{alloc, dealloc};
+use spin::Mutex;
+
+use super::paging::PAGE_SIZE;
+
+/// Object sizes of the general purpose caches of `SlabAllocator`.
+pub const SIZE_CLASSES: [usize; 7] = [32, 64, 128, 256, 512, 1024, 2048];
+
+/// Byte written over freed objects in debug builds, so that a use after free reads garbage that stands out
+/// and a write after free is caught when the object is handed out again.
+#[cfg(debug_assertions)]
+const POISON: u8 = 0x6B;
+
+/// Minimum number of objects a slab holds, slabs of large objects span several pages.
+const MIN_OBJECTS_PER_SLAB: usize = 8;
+
+/// General purpose caches for the kernel, over pages of the kernel heap.
+#[allow(dead_code)]
+pub static SLAB_ALLOCATOR: Mutex<SlabAllocator<HeapPages>> = Mutex::new(SlabAllocator::new(HeapPages));
+
+/// Where caches get the memory of their slabs from.
+pub trait PageSource {
+    /// Returns `size` bytes aligned on `size`, a power of two multiple of `PAGE_SIZE`.
+    fn allocate(&mut self, size: usize) -> Option<NonNull<u8>>;
+
+    /// Gives back memory returned by `allocate` with the same `size`.
+    ///
+    /// ## SAFETY
+    /// `ptr` must come from `allocate` on this source and not be used anymore.
+    unsafe fn free(&mut self, ptr: NonNull<u8>, size: usize);
+}
+
+/// Takes slabs from the global allocator, the kernel heap (or the host allocator under `cargo test`).
+pub struct HeapPages;
+
+impl PageSource for HeapPages {
+    fn allocate(&mut self, size: usize) -> Option<NonNull<u8>> {
+        NonNull::new(unsafe { alloc(Layout::from_size_align(size, size).ok()?) })
+    }
+
+    unsafe fn free(&mut self, ptr: NonNull<u8>, size: usize) {
+        dealloc(ptr.as_ptr(), Layout::from_size_align_unchecked(size, size));
+    }
+}
+
+/// Header at the start of every slab. The objects follow it, aligned on their size.
+struct Slab {
+    prev: *mut Slab,
+    next: *mut Slab,
+    /// First free object, each free object starts with a pointer to the next one.
+    free: *mut FreeObject,
+    in_use: usize,
+}
+
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+/// Counters of a cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub object_size: usize,
+    pub slabs: usize,
+    /// Objects the slabs can hold.
+    pub capacity: usize,
+    pub in_use: usize,
+    pub allocations: usize,
+    pub frees: usize,
+}
+
+/// A cache of objects of a single size, carved out of slabs of one or more pages.
+///
+/// Slabs with free objects are kept in `partial`, the others in `full`. A slab whose objects are
+/// all freed goes back to the page source, unless it is the only one left with free objects.
+///
+/// ### Example Usage:
+/// ```
+/// let mut tasks = Cache::with_constructor(128, |task| unsafe { task.as_ptr().write_bytes(0, 128) });
+/// let task = tasks.allocate(&mut HeapPages).unwrap();
+/// unsafe { tasks.free(&mut HeapPages, task) };
+/// ```
+pub struct Cache {
+    object_size: usize,
+    slab_size: usize,
+    /// Runs on every object before it is handed out.
+    constructor: Option<fn(NonNull<u8>)>,
+    partial: *mut Slab,
+    full: *mut Slab,
+    stats: CacheStats,
+}
+
+// SAFETY: The slabs are only reached through the cache, so moving it moves their ownership.
+unsafe impl Send for Cache {}
+
+#[allow(dead_code)]
+impl Cache {
+    /// Creates an empty cache, no memory is taken until the first allocation.
+    ///
+    /// ### Parameters:
+    /// - `object_size`: A power of two, at least the size of a pointer.
+    pub const fn new(object_size: usize) -> Self {
+        assert!(object_size.is_power_of_two() && object_size >= size_of::<FreeObject>());
+
+        let slab_size = object_size * MIN_OBJECTS_PER_SLAB;
+        Cache {
+            object_size,
+            slab_size: if slab_size > PAGE_SIZE { slab_size } else { PAGE_SIZE },
+            constructor: None,
+            partial: null_mut(),
+            full: null_mut(),
+            stats: CacheStats {
+                object_size,
+                slabs: 0,
+                capacity: 0,
+                in_use: 0,
+                allocations: 0,
+                frees: 0,
+            },
+        }
+    }
+
+    /// Creates a cache whose objects are initialized by `constructor` before being handed out.
+    pub const fn with_constructor(object_size: usize, constructor: fn(NonNull<u8>)) -> Self {
+        let mut cache = Cache::new(object_size);
+        cache.constructor = Some(constructor);
+        cache
+    }
+
+    pub fn object_size(&self) -> usize {
+        self.object_size
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Offset of the first object in a slab, the header rounded up to the object alignment.
+    fn first_object(&self) -> usize {
+        size_of::<Slab>().next_multiple_of(self.object_size)
+    }
+
+    fn objects_per_slab(&self) -> usize {
+        (self.slab_size - self.first_object()) / self.object_size
+    }
+
+    /// Returns an object, taking a new slab from `source` if every slab is full.
+    ///
+    /// ## Panics
+    /// In debug builds, if the object was written to after it was freed.
+    pub fn allocate(&mut self, source: &mut impl PageSource) -> Option<NonNull<u8>> {
+        unsafe {
+            if self.partial.is_null() {
+                let slab = self.new_slab(source)?;
+                push(&mut self.partial, slab);
+            }
+
+            let slab = self.partial;
+            let object = (*slab).free;
+            (*slab).free = (*object).next;
+            (*slab).in_use += 1;
+            if (*slab).free.is_null() {
+                remove(&mut self.partial, slab);
+                push(&mut self.full, slab);
+            }
+
+            let object = NonNull::new_unchecked(object as *mut u8);
+            #[cfg(debug_assertions)]
+            self.check_poison(object);
+            if let Some(constructor) = self.constructor {
+                constructor(object);
+            }
+
+            self.stats.in_use += 1;
+            self.stats.allocations += 1;
+            Some(object)
+        }
+    }
+
+    /// Gives back an object returned by `allocate`.
+    ///
+    /// ## SAFETY
+    /// `object` must come from `allocate` on this cache, with the same `source`, and not be freed already.
+    pub unsafe fn free(&mut self, source: &mut impl PageSource, object: NonNull<u8>) {
+        let slab = (object.as_ptr() as usize & !(self.slab_size - 1)) as *mut Slab;
+        let was_full = (*slab).free.is_null();
+
+        #[cfg(debug_assertions)]
+        object.as_ptr().write_bytes(POISON, self.object_size);
+        let object = object.as_ptr() as *mut FreeObject;
+        (*object).next = (*slab).free;
+        (*slab).free = object;
+        (*slab).in_use -= 1;
+        self.stats.in_use -= 1;
+        self.stats.frees += 1;
+
+        if was_full {
+            remove(&mut self.full, slab);
+            push(&mut self.partial, slab);
+        }
+        if (*slab).in_use == 0 && (self.partial != slab || !(*slab).next.is_null()) {
+            remove(&mut self.partial, slab);
+            source.free(NonNull::new_unchecked(slab as *mut u8), self.slab_size);
+            self.stats.slabs -= 1;
+            self.stats.capacity -= self.objects_per_slab();
+        }
+    }
+
+    /// Takes a slab from `source` and threads its objects into a free list.
+    unsafe fn new_slab(&mut self, source: &mut impl PageSource) -> Option<*mut Slab> {
+        let base = source.allocate(self.slab_size)?.as_ptr();
+        let mut free: *mut FreeObject = null_mut();
+
+        for index in (0..self.objects_per_slab()).rev() {
+            let object = base.add(self.first_object() + index * self.object_size);
+            #[cfg(debug_assertions)]
+            object.write_bytes(POISON, self.object_size);
+            let object = object as *mut FreeObject;
+            (*object).next = free;
+            free = object;
+        }
+
+        let slab = base as *mut Slab;
+        slab.write(Slab {
+            prev: null_mut(),
+            next: null_mut(),
+            free,
+            in_use: 0,
+        });
+        self.stats.slabs += 1;
+        self.stats.capacity += self.objects_per_slab();
+        Some(slab)
+    }
+
+    /// Panics if a byte of `object` past its free list pointer is not `POISON` anymore.
+    #[cfg(debug_assertions)]
+    unsafe fn check_poison(&self, object: NonNull<u8>) {
+        let bytes = core::slice::from_raw_parts(object.as_ptr(), self.object_size);
+
+        if let Some(offset) = bytes[size_of::<FreeObject>()..].iter().position(|&byte| byte != POISON) {
+            panic!(
+                "slab: object at {:p} of the {} bytes cache was written to after being freed, at offset {}",
+                object,
+                self.object_size,
+                offset + size_of::<FreeObject>()
+            );
+        }
+    }
+}
+
+/// Inserts `slab` at the front of `list`.
+unsafe fn push(list: &mut *mut Slab, slab: *mut Slab) {
+    (*slab).prev = null_mut();
+    (*slab).next = *list;
+    if !list.is_null() {
+        (**list).prev = slab;
+    }
+    *list = slab;
+}
+
+/// Unlinks `slab` from `list`, which must contain it.
+unsafe fn remove(list: &mut *mut Slab, slab: *mut Slab) {
+    if (*slab).prev.is_null() {
+        *list = (*slab).next;
+    } else {
+        (*(*slab).prev).next = (*slab).next;
+    }
+    if !(*slab).next.is_null() {
+        (*(*slab).next).prev = (*slab).prev;
+    }
+}
+
+/// A cache per size in `SIZE_CLASSES`, requests are served by the smallest one they fit in.
+pub struct SlabAllocator<S: PageSource> {
+    caches: [Cache; SIZE_CLASSES.len()],
+    source: S,
+}
+
+#[allow(dead_code)]
+impl<S: PageSource> SlabAllocator<S> {
+    pub const fn new(source: S) -> Self {
+        SlabAllocator {
+            caches: [
+                Cache::new(SIZE_CLASSES[0]),
+                Cache::new(SIZE_CLASSES[1]),
+                Cache::new(SIZE_CLASSES[2]),
+                Cache::new(SIZE_CLASSES[3]),
+                Cache::new(SIZE_CLASSES[4]),
+                Cache::new(SIZE_CLASSES[5]),
+                Cache::new(SIZE_CLASSES[6]),
+            ],
+            source,
+        }
+    }
+
+    /// Returns the index of the cache serving objects of `size` bytes.
+    fn cache_for(size: usize) -> Option<usize> {
+        SIZE_CLASSES.iter().position(|&class| size <= class)
+    }
+
+    /// Returns an object of at least `size` bytes, aligned on the size of its cache.
+    ///
+    /// ### Returns:
+    /// - `None` if `size` is larger than the largest size class or the page source is out of memory.
+    pub fn allocate(&mut self, size: usize) -> Option<NonNull<u8>> {
+        self.caches[Self::cache_for(size)?].allocate(&mut self.source)
+    }
+
+    /// Gives back an object returned by `allocate`.
+    ///
+    /// ## SAFETY
+    /// `object` must come from `allocate` on this allocator with the same `size`, and not be freed already.
+    pub unsafe fn free(&mut self, object: NonNull<u8>, size: usize) {
+        let index = Self::cache_for(size).expect("slab: freed object is larger than every cache");
+
+        self.caches[index].free(&mut self.source, object);
+    }
+
+    pub fn stats(&self) -> [CacheStats; SIZE_CLASSES.len()] {
+        self.caches.each_ref().map(Cache::stats)
+    }
+}
+
+#[cfg(test)]
+mod slab_test {
+    use super::*;
+
+    #[test]
+    fn test_size_classes() {
+        let mut slab = SlabAllocator::new(HeapPages);
+
+        for (size, class) in [(1, 32), (32, 32), (33, 64), (700, 1024), (2048, 2048)] {
+            let object = slab.allocate(size).unwrap();
+            assert_eq!(object.as_ptr() as usize % class, 0);
+            unsafe { slab.free(object, size) };
+        }
+        assert_eq!(slab.allocate(2049), None);
+    }
+
+    #[test]
+    fn test_slab_layout() {
+        assert_eq!(Cache::new(32).objects_per_slab(), (PAGE_SIZE - 32) / 32);
+        assert_eq!(Cache::new(2048).slab_size, 16 * 1024);
+        assert_eq!(Cache::new(2048).objects_per_slab(), 7);
+    }
+
+    #[test]
+    fn test_stats_and_slab_release() {
+        let mut cache = Cache::new(1024);
+        let per_slab = cache.objects_per_slab();
+
+        let objects: [NonNull<u8>; 8] = core::array::from_fn(|_| cache.allocate(&mut HeapPages).unwrap());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                object_size: 1024,
+                slabs: 2,
+                capacity: 2 * per_slab,
+                in_use: 8,
+                allocations: 8,
+                frees: 0,
+            }
+        );
+
+        for object in objects {
+            unsafe { cache.free(&mut HeapPages, object) };
+        }
+        // Both slabs emptied, only the last one with free objects is kept.
+        assert_eq!(cache.stats().slabs, 1);
+        assert_eq!(cache.stats().in_use, 0);
+        assert_eq!(cache.stats().frees, 8);
+    }
+
+    #[test]
+    fn test_freed_object_is_reused() {
+        let mut cache = Cache::new(64);
+
+        let first = cache.allocate(&mut HeapPages).unwrap();
+        let second = cache.allocate(&mut HeapPages).unwrap();
+        assert_ne!(first, second);
+        unsafe { cache.free(&mut HeapPages, first) };
+        assert_eq!(cache.allocate(&mut HeapPages), Some(first));
+    }
+
+    #[test]
+    fn test_constructor_runs_on_allocation() {
+        let mut cache = Cache::with_constructor(32, |object| unsafe { object.as_ptr().write_bytes(0xAB, 32) });
+
+        let object = cache.allocate(&mut HeapPages).unwrap();
+        let bytes = unsafe { core::slice::from_raw_parts(object.as_ptr(), 32) };
+        assert!(bytes.iter().all(|&byte| byte == 0xAB));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_freed_objects_are_poisoned() {
+        let mut cache = Cache::new(32);
+
+        let object = cache.allocate(&mut HeapPages).unwrap();
+        unsafe { cache.free(&mut HeapPages, object) };
+        let bytes = unsafe { core::slice::from_raw_parts(object.as_ptr(), 32) };
+        assert!(bytes[size_of::<FreeObject>()..].iter().all(|&byte| byte == POISON));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "written to after being freed")]
+    fn test_write_after_free_is_caught() {
+        let mut cache = Cache::new(32);
+
+        let object = cache.allocate(&mut HeapPages).unwrap();
+        unsafe {
+            cache.free(&mut HeapPages, object);
+            object.as_ptr().add(20).write(0);
+        }
+        cache.allocate(&mut HeapPages);
+    }
+}
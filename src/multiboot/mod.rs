@@ -0,0 +1,519 @@
+use core::ffi::CStr;
+
+use crate::{
+    print::{slice_to_str, u64_to_base},
+    terminal::{vga::Color, Terminal},
+};
+
+pub mod v1;
+pub mod v2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultibootError {
+    /// `EAX` held neither `v1::BOOTLOADER_MAGIC` nor `v2::BOOTLOADER_MAGIC`, the kernel was not started
+    /// by a Multiboot boot loader.
+    InvalidMagic(u32),
+    /// The boot loader passed a null information structure.
+    NullInfo,
+}
+
+impl MultibootError {
+    /// Writes the error in `Color::Error`.
+    pub fn write_to(&self, t: &mut Terminal) {
+        match *self {
+            MultibootError::InvalidMagic(magic) => {
+                let (slice, len) = u64_to_base(magic as u64, 16).unwrap();
+                t.write_color_str("not booted by a Multiboot boot loader, magic 0x", Color::Error as u8);
+                t.write_color_str(slice_to_str((&slice, len)).unwrap(), Color::Error as u8);
+            }
+            MultibootError::NullInfo => t.write_color_str("the boot loader passed no boot information", Color::Error as u8),
+        }
+        t.write_str("\n");
+    }
+}
+
+/// The information passed by the boot loader, in the format of the protocol the kernel was booted with.
+///
+/// Everything it returns is read in place from physical memory, so it borrows the `BootInfo`:
+/// `paging::init` takes the `BootInfo` when it drops the identity mapping, which ends those borrows.
+///
+/// `assets/boot.s` carries both a Multiboot and a Multiboot2 header, `grub.cfg` picks one with the
+/// `multiboot` or `multiboot2` command and the magic value in `EAX` tells which one was used.
+pub enum BootInfo {
+    Multiboot(v1::BootInfo),
+    Multiboot2(v2::BootInfo),
+}
+
+impl BootInfo {
+    /// Detects the boot protocol from the values `_start` received from the boot loader.
+    ///
+    /// ### Parameters:
+    /// - `magic`: The value of `EAX` at entry.
+    /// - `info`: The value of `EBX` at entry, the physical address of the information structure.
+    ///
+    /// ## SAFETY
+    /// If `magic` is valid, `info` and every address it contains must still be mapped at their physical
+    /// address and not have been overwritten, as the boot loader leaves them wherever it likes.
+    pub unsafe fn new(magic: u32, info: u32) -> Result<BootInfo, MultibootError> {
+        if magic != v1::BOOTLOADER_MAGIC && magic != v2::BOOTLOADER_MAGIC {
+            return Err(MultibootError::InvalidMagic(magic));
+        }
+        if info == 0 {
+            return Err(MultibootError::NullInfo);
+        }
+
+        if magic == v1::BOOTLOADER_MAGIC {
+            Ok(BootInfo::Multiboot(v1::BootInfo::new(info)))
+        } else {
+            Ok(BootInfo::Multiboot2(v2::BootInfo::new(info)))
+        }
+    }
+
+    pub fn protocol_name(&self) -> &'static str {
+        match self {
+            BootInfo::Multiboot(_) => "Multiboot",
+            BootInfo::Multiboot2(_) => "Multiboot2",
+        }
+    }
+
+    /// Returns the amount of lower (below 1 MiB) and upper (from 1 MiB to the first hole) memory, in KiB.
+    pub fn memory(&self) -> Option<(u32, u32)> {
+        match self {
+            BootInfo::Multiboot(info) => info.memory(),
+            BootInfo::Multiboot2(info) => info.memory(),
+        }
+    }
+
+    /// Returns the physical range `[start, end)` of the information structure, so it is not handed out
+    /// as free memory while it is still read.
+    pub fn footprint(&self) -> (usize, usize) {
+        match self {
+            BootInfo::Multiboot(info) => info.footprint(),
+            BootInfo::Multiboot2(info) => info.footprint(),
+        }
+    }
+
+    /// Returns the memory map provided by the firmware.
+    pub fn memory_map(&self) -> Option<MemoryMap<'_>> {
+        match self {
+            BootInfo::Multiboot(info) => info.memory_map().map(MemoryMap::V1),
+            BootInfo::Multiboot2(info) => info.memory_map().map(MemoryMap::V2),
+        }
+    }
+
+    /// Returns the command line the kernel was booted with, as written in `grub.cfg`.
+    pub fn cmdline(&self) -> Option<&str> {
+        match self {
+            BootInfo::Multiboot(info) => info.cmdline(),
+            BootInfo::Multiboot2(info) => info.cmdline(),
+        }
+    }
+
+    /// Returns the name of the boot loader, e.g. `GRUB 2.06`.
+    pub fn boot_loader_name(&self) -> Option<&str> {
+        match self {
+            BootInfo::Multiboot(info) => info.boot_loader_name(),
+            BootInfo::Multiboot2(info) => info.boot_loader_name(),
+        }
+    }
+
+    /// Returns the modules loaded next to the kernel.
+    pub fn modules(&self) -> Option<Modules<'_>> {
+        match self {
+            BootInfo::Multiboot(info) => info.modules().map(Modules::V1),
+            BootInfo::Multiboot2(info) => Some(Modules::V2(info.modules())),
+        }
+    }
+
+    /// Returns the framebuffer set up by the boot loader.
+    pub fn framebuffer(&self) -> Option<Framebuffer> {
+        match self {
+            BootInfo::Multiboot(info) => info.framebuffer(),
+            BootInfo::Multiboot2(info) => info.framebuffer(),
+        }
+    }
+
+    /// Returns the section headers of the kernel's ELF image.
+    pub fn elf_sections(&self) -> Option<ElfSections<'_>> {
+        match self {
+            BootInfo::Multiboot(info) => info.elf_sections(),
+            BootInfo::Multiboot2(info) => info.elf_sections(),
+        }
+    }
+
+    /// Returns the ACPI root pointer found by the boot loader. Only Multiboot2 passes it.
+    pub fn rsdp(&self) -> Option<Rsdp> {
+        match self {
+            BootInfo::Multiboot(_) => None,
+            BootInfo::Multiboot2(info) => info.rsdp(),
+        }
+    }
+
+    /// Writes what the boot loader told us, one line per item. Missing items are skipped.
+    ///
+    /// ### Parameters:
+    /// - `with_memory_map`: Also writes one line per memory map entry, which can take most of the screen.
+    pub fn write_to(&self, t: &mut Terminal, with_memory_map: bool) {
+        t.write_str("boot protocol: ");
+        t.write_str(self.protocol_name());
+        t.write_str("\n");
+
+        if let Some(name) = self.boot_loader_name() {
+            t.write_str("boot loader: ");
+            t.write_str(name);
+            t.write_str("\n");
+        }
+        if let Some(cmdline) = self.cmdline() {
+            t.write_str("cmdline: ");
+            t.write_str(cmdline);
+            t.write_str("\n");
+        }
+        if let Some((lower, upper)) = self.memory() {
+            write_number(t, "memory: ", lower as u64, 10);
+            write_number(t, " KiB lower, ", upper as u64, 10);
+            t.write_str(" KiB upper\n");
+        }
+        if let Some(map) = self.memory_map().filter(|_| with_memory_map) {
+            for region in map {
+                write_number(t, "  0x", region.base, 16);
+                write_number(t, "-0x", region.end() - 1, 16);
+                t.write_str(" ");
+                t.write_str(region.kind.name());
+                t.write_str("\n");
+            }
+        }
+        if let Some(modules) = self.modules() {
+            for module in modules {
+                write_number(t, "module 0x", module.start as u64, 16);
+                write_number(t, "-0x", module.end as u64, 16);
+                if let Some(cmdline) = module.cmdline {
+                    t.write_str(" ");
+                    t.write_str(cmdline);
+                }
+                t.write_str("\n");
+            }
+        }
+        if let Some(fb) = self.framebuffer() {
+            write_number(t, "framebuffer: ", fb.width as u64, 10);
+            write_number(t, "x", fb.height as u64, 10);
+            t.write_str(" ");
+            t.write_str(fb.kind.name());
+            write_number(t, " at 0x", fb.addr, 16);
+            t.write_str("\n");
+        }
+        if let Some(sections) = self.elf_sections() {
+            write_number(t, "ELF sections: ", sections.count() as u64, 10);
+            t.write_str("\n");
+        }
+        if let Some(rsdp) = self.rsdp() {
+            write_number(t, "ACPI: revision ", rsdp.revision as u64, 10);
+            write_number(t, ", RSDT at 0x", rsdp.rsdt_address as u64, 16);
+            t.write_str("\n");
+        }
+    }
+}
+
+/// Writes `prefix` followed by `value` in `base`.
+fn write_number(t: &mut Terminal, prefix: &str, value: u64, base: u8) {
+    let (slice, len) = u64_to_base(value, base).unwrap();
+
+    t.write_str(prefix);
+    t.write_str(slice_to_str((&slice, len)).unwrap());
+}
+
+/// Reads the NUL terminated string at physical address `addr`. Strings that are not valid UTF-8 are ignored.
+///
+/// ## SAFETY
+/// `addr` must be 0 or point to a NUL terminated string that stays mapped and unchanged for `'a`.
+unsafe fn c_str<'a>(addr: u32) -> Option<&'a str> {
+    if addr == 0 {
+        return None;
+    }
+
+    CStr::from_ptr(addr as usize as *const _).to_str().ok()
+}
+
+/// Reads a NUL terminated string stored inline in `bytes`, as Multiboot2 tags do.
+fn inline_c_str(bytes: &[u8]) -> Option<&str> {
+    CStr::from_bytes_until_nul(bytes).ok()?.to_str().ok()
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Type of a memory map region. Both protocols use the same values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryKind {
+    /// RAM free for the kernel to use.
+    Available,
+    Reserved,
+    /// ACPI tables, usable once they were parsed.
+    AcpiReclaimable,
+    /// Must be preserved across sleep states.
+    AcpiNvs,
+    /// Defective RAM.
+    BadMemory,
+    /// A type this kernel does not know, to be treated as reserved.
+    Unknown(u32),
+}
+
+impl MemoryKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryKind::Available => "available",
+            MemoryKind::Reserved => "reserved",
+            MemoryKind::AcpiReclaimable => "ACPI reclaimable",
+            MemoryKind::AcpiNvs => "ACPI NVS",
+            MemoryKind::BadMemory => "bad memory",
+            MemoryKind::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl From<u32> for MemoryKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            1 => MemoryKind::Available,
+            2 => MemoryKind::Reserved,
+            3 => MemoryKind::AcpiReclaimable,
+            4 => MemoryKind::AcpiNvs,
+            5 => MemoryKind::BadMemory,
+            kind => MemoryKind::Unknown(kind),
+        }
+    }
+}
+
+/// A range of physical memory described by the memory map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: u64,
+    pub length: u64,
+    pub kind: MemoryKind,
+}
+
+impl MemoryRegion {
+    /// Returns the address right after the region.
+    pub fn end(&self) -> u64 {
+        self.base + self.length
+    }
+}
+
+/// Iterator over the memory map, whatever the protocol.
+pub enum MemoryMap<'a> {
+    V1(v1::MemoryMap<'a>),
+    V2(v2::MemoryMap<'a>),
+}
+
+impl Iterator for MemoryMap<'_> {
+    type Item = MemoryRegion;
+
+    fn next(&mut self) -> Option<MemoryRegion> {
+        match self {
+            MemoryMap::V1(map) => map.next(),
+            MemoryMap::V2(map) => map.next(),
+        }
+    }
+}
+
+/// A file loaded by the boot loader next to the kernel, e.g. with `module` in `grub.cfg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Module<'a> {
+    /// Physical address of the first byte.
+    pub start: u32,
+    /// Physical address right after the last byte.
+    pub end: u32,
+    /// The arguments given to the module in `grub.cfg`.
+    pub cmdline: Option<&'a str>,
+}
+
+/// Iterator over the modules, whatever the protocol.
+pub enum Modules<'a> {
+    V1(v1::Modules<'a>),
+    V2(v2::Modules<'a>),
+}
+
+impl<'a> Iterator for Modules<'a> {
+    type Item = Module<'a>;
+
+    fn next(&mut self) -> Option<Module<'a>> {
+        match self {
+            Modules::V1(modules) => modules.next(),
+            Modules::V2(modules) => modules.next(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferKind {
+    /// Graphics mode with a palette.
+    Indexed,
+    /// Graphics mode with direct RGB colors.
+    Rgb,
+    /// VGA text mode, `width` and `height` are in characters.
+    EgaText,
+    Unknown(u8),
+}
+
+impl FramebufferKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FramebufferKind::Indexed => "indexed",
+            FramebufferKind::Rgb => "RGB",
+            FramebufferKind::EgaText => "EGA text",
+            FramebufferKind::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl From<u8> for FramebufferKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => FramebufferKind::Indexed,
+            1 => FramebufferKind::Rgb,
+            2 => FramebufferKind::EgaText,
+            kind => FramebufferKind::Unknown(kind),
+        }
+    }
+}
+
+/// The video mode set up by the boot loader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+    /// Physical address of the framebuffer.
+    pub addr: u64,
+    /// Bytes per line.
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Bits per pixel, 16 (character and attribute) in text mode.
+    pub bpp: u8,
+    pub kind: FramebufferKind,
+}
+
+/// Size of a 32-bit ELF section header.
+const ELF_SECTION_HEADER_SIZE: usize = 40;
+
+/// A section header of the kernel's ELF image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElfSection {
+    /// Offset of the name in the section name string table.
+    pub name_offset: u32,
+    pub kind: u32,
+    pub flags: u32,
+    /// Address of the section in memory, 0 if it is not loaded.
+    pub addr: u32,
+    pub size: u32,
+}
+
+/// Iterator over the section headers of the kernel's ELF image, both protocols pass the same table.
+pub struct ElfSections<'a> {
+    headers: &'a [u8],
+    entry_size: usize,
+    /// Index of the section holding the section names.
+    string_table: usize,
+}
+
+impl<'a> ElfSections<'a> {
+    /// ### Parameters:
+    /// - `headers`: The section header table.
+    /// - `entry_size`: Size of one header, at least `ELF_SECTION_HEADER_SIZE`.
+    /// - `string_table`: Index of the section name string table.
+    fn new(headers: &'a [u8], entry_size: usize, string_table: usize) -> Self {
+        ElfSections {
+            headers,
+            entry_size: entry_size.max(ELF_SECTION_HEADER_SIZE),
+            string_table,
+        }
+    }
+
+    /// Returns the name of `section`, read from the string table loaded by the boot loader.
+    #[allow(dead_code)]
+    pub fn name(&self, section: &ElfSection) -> Option<&'a str> {
+        let offset = self.string_table.checked_mul(self.entry_size)?;
+        let string_table = parse_section_header(self.headers.get(offset..)?)?;
+        if string_table.addr == 0 || section.name_offset >= string_table.size {
+            return None;
+        }
+
+        unsafe { c_str(string_table.addr + section.name_offset) }
+    }
+}
+
+impl Iterator for ElfSections<'_> {
+    type Item = ElfSection;
+
+    fn next(&mut self) -> Option<ElfSection> {
+        let section = parse_section_header(self.headers)?;
+
+        self.headers = self.headers.get(self.entry_size..).unwrap_or(&[]);
+        Some(section)
+    }
+}
+
+fn parse_section_header(bytes: &[u8]) -> Option<ElfSection> {
+    if bytes.len() < ELF_SECTION_HEADER_SIZE {
+        return None;
+    }
+
+    Some(ElfSection {
+        name_offset: u32_at(bytes, 0),
+        kind: u32_at(bytes, 4),
+        flags: u32_at(bytes, 8),
+        addr: u32_at(bytes, 12),
+        size: u32_at(bytes, 20),
+    })
+}
+
+/// The ACPI Root System Description Pointer, copied by the boot loader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rsdp {
+    /// 0 for ACPI 1.0, 2 from ACPI 2.0 on.
+    pub revision: u8,
+    pub oem_id: [u8; 6],
+    /// Physical address of the RSDT.
+    pub rsdt_address: u32,
+    /// Physical address of the XSDT, from revision 2 on.
+    pub xsdt_address: Option<u64>,
+}
+
+#[cfg(test)]
+mod multiboot_test {
+    use super::*;
+
+    #[test]
+    fn test_kinds() {
+        assert_eq!(MemoryKind::from(3), MemoryKind::AcpiReclaimable);
+        assert_eq!(MemoryKind::from(42), MemoryKind::Unknown(42));
+        assert_eq!(FramebufferKind::from(2), FramebufferKind::EgaText);
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        assert_eq!(unsafe { BootInfo::new(0x1234, 0x10000) }.err(), Some(MultibootError::InvalidMagic(0x1234)));
+        assert_eq!(unsafe { BootInfo::new(v1::BOOTLOADER_MAGIC, 0) }.err(), Some(MultibootError::NullInfo));
+        assert_eq!(unsafe { BootInfo::new(v2::BOOTLOADER_MAGIC, 0) }.err(), Some(MultibootError::NullInfo));
+    }
+
+    #[test]
+    fn test_elf_sections() {
+        /// A null section followed by a section named at offset 7, loaded at 0x100000 and 0x2000 bytes long.
+        static HEADERS: [u8; 2 * ELF_SECTION_HEADER_SIZE] = {
+            let mut headers = [0; 2 * ELF_SECTION_HEADER_SIZE];
+            headers[ELF_SECTION_HEADER_SIZE] = 7;
+            headers[ELF_SECTION_HEADER_SIZE + 14] = 0x10;
+            headers[ELF_SECTION_HEADER_SIZE + 21] = 0x20;
+            headers
+        };
+
+        let mut sections = ElfSections::new(&HEADERS, ELF_SECTION_HEADER_SIZE, 0);
+        assert_eq!(sections.next().map(|s| s.addr), Some(0));
+        let text = sections.next().unwrap();
+        assert_eq!((text.name_offset, text.addr, text.size), (7, 0x100000, 0x2000));
+        assert_eq!(sections.next(), None);
+    }
+}
@@ -0,0 +1,268 @@
+use core::{mem::size_of, slice};
+
+use super::{c_str, u32_at, u64_at, ElfSections, Framebuffer, FramebufferKind, MemoryKind, MemoryRegion, Module};
+
+/// Value of `EAX` when a Multiboot compliant boot loader jumps to `_start`.
+pub const BOOTLOADER_MAGIC: u32 = 0x2BAD_B002;
+
+/// `flags`: `mem_lower` and `mem_upper` are valid.
+const FLAG_MEMORY: u32 = 1 << 0;
+/// `flags`: `cmdline` is valid.
+const FLAG_CMDLINE: u32 = 1 << 2;
+/// `flags`: `mods_count` and `mods_addr` are valid.
+const FLAG_MODULES: u32 = 1 << 3;
+/// `flags`: `syms` holds the ELF section header table.
+const FLAG_ELF_SECTIONS: u32 = 1 << 5;
+/// `flags`: `mmap_length` and `mmap_addr` are valid.
+const FLAG_MEMORY_MAP: u32 = 1 << 6;
+/// `flags`: `boot_loader_name` is valid.
+const FLAG_BOOT_LOADER_NAME: u32 = 1 << 9;
+/// `flags`: the `framebuffer_*` fields are valid.
+const FLAG_FRAMEBUFFER: u32 = 1 << 12;
+
+/// Size of a module entry: start, end, string and a reserved field.
+const MODULE_ENTRY_SIZE: usize = 16;
+
+/// The [Multiboot information structure](https://www.gnu.org/software/grub/manual/multiboot/multiboot.html#Boot-information-format)
+/// as laid out in memory by the boot loader. Fields are only valid if their bit is set in `flags`.
+#[repr(C, packed)]
+struct RawInfo {
+    flags: u32,
+    mem_lower: u32,
+    mem_upper: u32,
+    boot_device: u32,
+    cmdline: u32,
+    mods_count: u32,
+    mods_addr: u32,
+    /// With `FLAG_ELF_SECTIONS`: number of headers, size of a header, address of the table and
+    /// index of the section name string table.
+    syms: [u32; 4],
+    mmap_length: u32,
+    mmap_addr: u32,
+    drives_length: u32,
+    drives_addr: u32,
+    config_table: u32,
+    boot_loader_name: u32,
+    apm_table: u32,
+    vbe_control_info: u32,
+    vbe_mode_info: u32,
+    vbe_mode: u16,
+    vbe_interface_seg: u16,
+    vbe_interface_off: u16,
+    vbe_interface_len: u16,
+    framebuffer_addr: u64,
+    framebuffer_pitch: u32,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    framebuffer_bpp: u8,
+    framebuffer_type: u8,
+    color_info: [u8; 6],
+}
+
+/// Typed view over the information passed by a Multiboot boot loader.
+pub struct BootInfo {
+    raw: &'static RawInfo,
+}
+
+impl BootInfo {
+    /// ## SAFETY
+    /// `info` must be the address of the information structure passed by a Multiboot boot loader,
+    /// see `multiboot::BootInfo::new`.
+    pub unsafe fn new(info: u32) -> BootInfo {
+        BootInfo {
+            raw: &*(info as usize as *const RawInfo),
+        }
+    }
+
+    pub fn footprint(&self) -> (usize, usize) {
+        let start = self.raw as *const RawInfo as usize;
+
+        (start, start + size_of::<RawInfo>())
+    }
+
+    fn has(&self, flag: u32) -> bool {
+        self.raw.flags & flag != 0
+    }
+
+    pub fn memory(&self) -> Option<(u32, u32)> {
+        if !self.has(FLAG_MEMORY) {
+            return None;
+        }
+
+        Some((self.raw.mem_lower, self.raw.mem_upper))
+    }
+
+    pub fn memory_map(&self) -> Option<MemoryMap<'_>> {
+        if !self.has(FLAG_MEMORY_MAP) {
+            return None;
+        }
+
+        let bytes = unsafe { slice::from_raw_parts(self.raw.mmap_addr as usize as *const u8, self.raw.mmap_length as usize) };
+        Some(MemoryMap::new(bytes))
+    }
+
+    pub fn cmdline(&self) -> Option<&str> {
+        if !self.has(FLAG_CMDLINE) {
+            return None;
+        }
+
+        unsafe { c_str(self.raw.cmdline) }
+    }
+
+    pub fn boot_loader_name(&self) -> Option<&str> {
+        if !self.has(FLAG_BOOT_LOADER_NAME) {
+            return None;
+        }
+
+        unsafe { c_str(self.raw.boot_loader_name) }
+    }
+
+    pub fn modules(&self) -> Option<Modules<'_>> {
+        if !self.has(FLAG_MODULES) {
+            return None;
+        }
+
+        let len = self.raw.mods_count as usize * MODULE_ENTRY_SIZE;
+        let bytes = unsafe { slice::from_raw_parts(self.raw.mods_addr as usize as *const u8, len) };
+        Some(Modules { bytes })
+    }
+
+    pub fn framebuffer(&self) -> Option<Framebuffer> {
+        if !self.has(FLAG_FRAMEBUFFER) {
+            return None;
+        }
+
+        Some(Framebuffer {
+            addr: self.raw.framebuffer_addr,
+            pitch: self.raw.framebuffer_pitch,
+            width: self.raw.framebuffer_width,
+            height: self.raw.framebuffer_height,
+            bpp: self.raw.framebuffer_bpp,
+            kind: FramebufferKind::from(self.raw.framebuffer_type),
+        })
+    }
+
+    pub fn elf_sections(&self) -> Option<ElfSections<'_>> {
+        if !self.has(FLAG_ELF_SECTIONS) {
+            return None;
+        }
+
+        let [count, entry_size, addr, string_table] = self.raw.syms;
+        let bytes = unsafe { slice::from_raw_parts(addr as usize as *const u8, count as usize * entry_size as usize) };
+        Some(ElfSections::new(bytes, entry_size as usize, string_table as usize))
+    }
+}
+
+/// Iterator over the entries of the Multiboot memory map.
+///
+/// Each entry starts with its size, not counting the size field itself, so entries larger
+/// than `base`, `length` and `type` are skipped correctly.
+pub struct MemoryMap<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> MemoryMap<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        MemoryMap { bytes }
+    }
+}
+
+impl Iterator for MemoryMap<'_> {
+    type Item = MemoryRegion;
+
+    fn next(&mut self) -> Option<MemoryRegion> {
+        const ENTRY_SIZE: usize = size_of::<u32>() + 2 * size_of::<u64>() + size_of::<u32>();
+
+        if self.bytes.len() < ENTRY_SIZE {
+            return None;
+        }
+
+        let size = u32_at(self.bytes, 0) as usize + size_of::<u32>();
+        let region = MemoryRegion {
+            base: u64_at(self.bytes, 4),
+            length: u64_at(self.bytes, 12),
+            kind: MemoryKind::from(u32_at(self.bytes, 20)),
+        };
+
+        self.bytes = self.bytes.get(size.max(ENTRY_SIZE)..).unwrap_or(&[]);
+        Some(region)
+    }
+}
+
+/// Iterator over the module entries.
+pub struct Modules<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for Modules<'a> {
+    type Item = Module<'a>;
+
+    fn next(&mut self) -> Option<Module<'a>> {
+        if self.bytes.len() < MODULE_ENTRY_SIZE {
+            return None;
+        }
+
+        let module = Module {
+            start: u32_at(self.bytes, 0),
+            end: u32_at(self.bytes, 4),
+            cmdline: unsafe { c_str(u32_at(self.bytes, 8)) },
+        };
+
+        self.bytes = &self.bytes[MODULE_ENTRY_SIZE..];
+        Some(module)
+    }
+}
+
+#[cfg(test)]
+mod v1_test {
+    use super::*;
+
+    /// Two entries as GRUB lays them out, the second one with 4 bytes of padding announced by its size.
+    const MEMORY_MAP: [u8; 52] = [
+        20, 0, 0, 0, // size
+        0, 0, 0, 0, 0, 0, 0, 0, // base 0
+        0x00, 0xFC, 0x09, 0, 0, 0, 0, 0, // length 0x9FC00
+        1, 0, 0, 0, // available
+        24, 0, 0, 0, // size
+        0, 0, 0x10, 0, 0, 0, 0, 0, // base 0x100000
+        0, 0, 0xF0, 0x07, 0, 0, 0, 0, // length 0x7F00000
+        2, 0, 0, 0, // reserved
+        0xFF, 0xFF, 0xFF, 0xFF, // padding
+    ];
+
+    #[test]
+    fn test_memory_map() {
+        let mut map = MemoryMap::new(&MEMORY_MAP);
+
+        let first = map.next().unwrap();
+        assert_eq!((first.base, first.end(), first.kind), (0, 0x9FC00, MemoryKind::Available));
+        let second = map.next().unwrap();
+        assert_eq!((second.base, second.length, second.kind), (0x100000, 0x7F00000, MemoryKind::Reserved));
+        assert_eq!(map.next(), None);
+    }
+
+    #[test]
+    fn test_memory_map_ignores_truncated_entry() {
+        assert_eq!(MemoryMap::new(&MEMORY_MAP[..20]).next(), None);
+    }
+
+    #[test]
+    fn test_modules() {
+        const MODULES: [u8; 32] = [
+            0x00, 0x00, 0x20, 0x00, 0x00, 0x10, 0x20, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, //
+            0x00, 0x10, 0x20, 0x00, 0x34, 0x12, 0x20, 0x00, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut modules = Modules { bytes: &MODULES };
+
+        assert_eq!(
+            modules.next(),
+            Some(Module {
+                start: 0x200000,
+                end: 0x201000,
+                cmdline: None
+            })
+        );
+        assert_eq!(modules.next().map(|m| m.end), Some(0x201234));
+        assert_eq!(modules.next(), None);
+    }
+}
@@ -0,0 +1,341 @@
+use core::slice;
+
+use super::{inline_c_str, u32_at, u64_at, ElfSections, Framebuffer, FramebufferKind, MemoryKind, MemoryRegion, Module, Rsdp};
+
+/// Value of `EAX` when a Multiboot2 compliant boot loader jumps to `_start`.
+pub const BOOTLOADER_MAGIC: u32 = 0x36D7_6289;
+
+/// Size of the fixed part of the information structure: its total size and a reserved field.
+const INFO_HEADER_SIZE: usize = 8;
+/// Size of the header every tag starts with: its type and its size.
+const TAG_HEADER_SIZE: usize = 8;
+/// Tags start on 8 byte boundaries.
+const TAG_ALIGN: usize = 8;
+
+const TAG_END: u32 = 0;
+const TAG_CMDLINE: u32 = 1;
+const TAG_BOOT_LOADER_NAME: u32 = 2;
+const TAG_MODULE: u32 = 3;
+const TAG_BASIC_MEMORY: u32 = 4;
+const TAG_MEMORY_MAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_ELF_SECTIONS: u32 = 9;
+const TAG_ACPI_OLD_RSDP: u32 = 14;
+const TAG_ACPI_NEW_RSDP: u32 = 15;
+
+/// Typed view over the tags passed by a Multiboot2 boot loader, see the
+/// [specification](https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#Boot-information-format).
+pub struct BootInfo {
+    bytes: &'static [u8],
+}
+
+impl BootInfo {
+    /// ## SAFETY
+    /// `info` must be the address of the information structure passed by a Multiboot2 boot loader,
+    /// see `multiboot::BootInfo::new`.
+    pub unsafe fn new(info: u32) -> BootInfo {
+        let total_size = *(info as usize as *const u32);
+
+        BootInfo::from_bytes(slice::from_raw_parts(info as usize as *const u8, total_size as usize))
+    }
+
+    fn from_bytes(bytes: &'static [u8]) -> BootInfo {
+        BootInfo { bytes }
+    }
+
+    pub fn footprint(&self) -> (usize, usize) {
+        let start = self.bytes.as_ptr() as usize;
+
+        (start, start + self.bytes.len())
+    }
+
+    /// Returns an iterator over the tags, without the fixed header of the structure.
+    fn tags(&self) -> Tags<'_> {
+        Tags {
+            bytes: self.bytes.get(INFO_HEADER_SIZE..).unwrap_or(&[]),
+        }
+    }
+
+    /// Returns the first tag of type `kind`, header included.
+    fn find(&self, kind: u32) -> Option<&[u8]> {
+        self.tags().find(|tag| tag.kind == kind).map(|tag| tag.bytes)
+    }
+
+    pub fn memory(&self) -> Option<(u32, u32)> {
+        let tag = self.find(TAG_BASIC_MEMORY)?;
+        if tag.len() < 16 {
+            return None;
+        }
+
+        Some((u32_at(tag, 8), u32_at(tag, 12)))
+    }
+
+    pub fn memory_map(&self) -> Option<MemoryMap<'_>> {
+        let tag = self.find(TAG_MEMORY_MAP)?;
+        if tag.len() < 16 {
+            return None;
+        }
+
+        Some(MemoryMap {
+            entries: tag.get(16..)?,
+            entry_size: u32_at(tag, 8) as usize,
+        })
+    }
+
+    pub fn cmdline(&self) -> Option<&str> {
+        inline_c_str(self.find(TAG_CMDLINE)?.get(TAG_HEADER_SIZE..)?)
+    }
+
+    pub fn boot_loader_name(&self) -> Option<&str> {
+        inline_c_str(self.find(TAG_BOOT_LOADER_NAME)?.get(TAG_HEADER_SIZE..)?)
+    }
+
+    /// Unlike Multiboot, every module has its own tag. The iterator is empty if none was loaded.
+    pub fn modules(&self) -> Modules<'_> {
+        Modules { tags: self.tags() }
+    }
+
+    pub fn framebuffer(&self) -> Option<Framebuffer> {
+        let tag = self.find(TAG_FRAMEBUFFER)?;
+        if tag.len() < 30 {
+            return None;
+        }
+
+        Some(Framebuffer {
+            addr: u64_at(tag, 8),
+            pitch: u32_at(tag, 16),
+            width: u32_at(tag, 20),
+            height: u32_at(tag, 24),
+            bpp: tag[28],
+            kind: FramebufferKind::from(tag[29]),
+        })
+    }
+
+    pub fn elf_sections(&self) -> Option<ElfSections<'_>> {
+        let tag = self.find(TAG_ELF_SECTIONS)?;
+        if tag.len() < 20 {
+            return None;
+        }
+
+        Some(ElfSections::new(tag.get(20..)?, u32_at(tag, 12) as usize, u32_at(tag, 16) as usize))
+    }
+
+    /// Returns the ACPI 2.0+ root pointer if the boot loader found one, the ACPI 1.0 one otherwise.
+    pub fn rsdp(&self) -> Option<Rsdp> {
+        if let Some(tag) = self.find(TAG_ACPI_NEW_RSDP) {
+            return parse_rsdp(tag.get(TAG_HEADER_SIZE..)?);
+        }
+
+        parse_rsdp(self.find(TAG_ACPI_OLD_RSDP)?.get(TAG_HEADER_SIZE..)?)
+    }
+}
+
+/// Parses a copy of the RSDP: `RSD PTR ` signature, checksum, OEM ID, revision and RSDT address,
+/// then from revision 2 on the length and the XSDT address.
+fn parse_rsdp(bytes: &[u8]) -> Option<Rsdp> {
+    const RSDP_V1_SIZE: usize = 20;
+    const RSDP_V2_SIZE: usize = 36;
+
+    if bytes.len() < RSDP_V1_SIZE || &bytes[..8] != b"RSD PTR " {
+        return None;
+    }
+
+    let revision = bytes[15];
+    Some(Rsdp {
+        revision,
+        oem_id: bytes[9..15].try_into().unwrap(),
+        rsdt_address: u32_at(bytes, 16),
+        xsdt_address: if revision >= 2 && bytes.len() >= RSDP_V2_SIZE {
+            Some(u64_at(bytes, 24))
+        } else {
+            None
+        },
+    })
+}
+
+struct Tag<'a> {
+    kind: u32,
+    /// The whole tag, header included.
+    bytes: &'a [u8],
+}
+
+/// Iterator over the tags, stopping at the end tag or at a tag that does not fit in the structure.
+struct Tags<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Tag<'a>;
+
+    fn next(&mut self) -> Option<Tag<'a>> {
+        if self.bytes.len() < TAG_HEADER_SIZE {
+            return None;
+        }
+
+        let kind = u32_at(self.bytes, 0);
+        let size = u32_at(self.bytes, 4) as usize;
+        if kind == TAG_END || size < TAG_HEADER_SIZE || size > self.bytes.len() {
+            return None;
+        }
+
+        let tag = Tag {
+            kind,
+            bytes: &self.bytes[..size],
+        };
+        self.bytes = self.bytes.get(size.next_multiple_of(TAG_ALIGN)..).unwrap_or(&[]);
+        Some(tag)
+    }
+}
+
+/// Iterator over the entries of the memory map tag, which all have the size announced in the tag.
+pub struct MemoryMap<'a> {
+    entries: &'a [u8],
+    entry_size: usize,
+}
+
+impl Iterator for MemoryMap<'_> {
+    type Item = MemoryRegion;
+
+    fn next(&mut self) -> Option<MemoryRegion> {
+        const ENTRY_SIZE: usize = 24;
+
+        if self.entries.len() < ENTRY_SIZE {
+            return None;
+        }
+
+        let region = MemoryRegion {
+            base: u64_at(self.entries, 0),
+            length: u64_at(self.entries, 8),
+            kind: MemoryKind::from(u32_at(self.entries, 16)),
+        };
+
+        self.entries = self.entries.get(self.entry_size.max(ENTRY_SIZE)..).unwrap_or(&[]);
+        Some(region)
+    }
+}
+
+/// Iterator over the module tags.
+pub struct Modules<'a> {
+    tags: Tags<'a>,
+}
+
+impl<'a> Iterator for Modules<'a> {
+    type Item = Module<'a>;
+
+    fn next(&mut self) -> Option<Module<'a>> {
+        let tag = self.tags.find(|tag| tag.kind == TAG_MODULE && tag.bytes.len() >= 16)?;
+
+        Some(Module {
+            start: u32_at(tag.bytes, 8),
+            end: u32_at(tag.bytes, 12),
+            cmdline: inline_c_str(&tag.bytes[16..]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod v2_test {
+    use super::*;
+
+    /// Information structure with a command line, a module, a memory map and an ACPI 1.0 RSDP,
+    /// as GRUB lays it out. Wrapped to get the 8 byte alignment of the real structure.
+    #[repr(C, align(8))]
+    struct Info<const N: usize>([u8; N]);
+
+    static INFO: Info<160> = Info([
+        160, 0, 0, 0, 0, 0, 0, 0, // total size, reserved
+        1, 0, 0, 0, 18, 0, 0, 0, // cmdline tag
+        b'l', b'a', b'y', b'o', b'u', b't', b'=', b'f', b'r', 0, 0, 0, 0, 0, 0, 0, // padded to 8 bytes
+        3, 0, 0, 0, 21, 0, 0, 0, // module tag
+        0, 0, 0x20, 0, 0, 0x10, 0x20, 0, // start, end
+        b'i', b'n', b'i', b't', 0, 0, 0, 0, // string, padded
+        6, 0, 0, 0, 64, 0, 0, 0, // memory map tag
+        24, 0, 0, 0, 0, 0, 0, 0, // entry size, entry version
+        0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0xFC, 0x09, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, // available
+        0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0xF0, 0x07, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, // reserved
+        14, 0, 0, 0, 28, 0, 0, 0, // old RSDP tag
+        b'R', b'S', b'D', b' ', b'P', b'T', b'R', b' ', 0, b'B', b'O', b'C', b'H', b'S', b' ', 0, // signature, checksum, OEM, revision
+        0x00, 0x00, 0xFE, 0x07, 0, 0, 0, 0, // RSDT address, padding
+        0, 0, 0, 0, 8, 0, 0, 0, // end tag
+    ]);
+
+    /// Basic memory, memory map and ELF sections tags, each cut before its last field.
+    static TRUNCATED_TAGS: Info<64> = Info([
+        64, 0, 0, 0, 0, 0, 0, 0, // total size, reserved
+        4, 0, 0, 0, 12, 0, 0, 0, // basic memory tag
+        0x80, 0x02, 0, 0, 0, 0, 0, 0, // lower memory, padding
+        6, 0, 0, 0, 12, 0, 0, 0, // memory map tag
+        24, 0, 0, 0, 0, 0, 0, 0, // entry size, padding
+        9, 0, 0, 0, 16, 0, 0, 0, // ELF sections tag
+        1, 0, 0, 0, 40, 0, 0, 0, // number of sections, entry size
+        0, 0, 0, 0, 8, 0, 0, 0, // end tag
+    ]);
+
+    #[test]
+    fn test_strings() {
+        let info = BootInfo::from_bytes(&INFO.0);
+
+        assert_eq!(info.cmdline(), Some("layout=fr"));
+        assert_eq!(info.boot_loader_name(), None);
+    }
+
+    #[test]
+    fn test_modules() {
+        let info = BootInfo::from_bytes(&INFO.0);
+        let mut modules = info.modules();
+
+        assert_eq!(
+            modules.next(),
+            Some(Module {
+                start: 0x200000,
+                end: 0x201000,
+                cmdline: Some("init"),
+            })
+        );
+        assert_eq!(modules.next(), None);
+    }
+
+    #[test]
+    fn test_memory_map() {
+        let info = BootInfo::from_bytes(&INFO.0);
+        let mut map = info.memory_map().unwrap();
+
+        assert_eq!(map.next().map(|r| (r.base, r.end(), r.kind)), Some((0, 0x9FC00, MemoryKind::Available)));
+        assert_eq!(
+            map.next().map(|r| (r.base, r.length, r.kind)),
+            Some((0x100000, 0x7F00000, MemoryKind::Reserved))
+        );
+        assert_eq!(map.next(), None);
+    }
+
+    #[test]
+    fn test_rsdp() {
+        let rsdp = BootInfo::from_bytes(&INFO.0).rsdp().unwrap();
+
+        assert_eq!(rsdp.revision, 0);
+        assert_eq!(&rsdp.oem_id, b"BOCHS ");
+        assert_eq!(rsdp.rsdt_address, 0x07FE0000);
+        assert_eq!(rsdp.xsdt_address, None);
+    }
+
+    #[test]
+    fn test_missing_tags_and_truncated_structure() {
+        let info = BootInfo::from_bytes(&INFO.0);
+        assert_eq!(info.framebuffer(), None);
+        assert!(info.elf_sections().is_none());
+
+        // The module tag claims more bytes than what is left.
+        let truncated = BootInfo::from_bytes(&INFO.0[..40]);
+        assert_eq!(truncated.cmdline(), Some("layout=fr"));
+        assert_eq!(truncated.modules().next(), None);
+    }
+
+    #[test]
+    fn test_truncated_tags() {
+        let info = BootInfo::from_bytes(&TRUNCATED_TAGS.0);
+
+        assert_eq!(info.memory(), None);
+        assert!(info.memory_map().is_none());
+        assert!(info.elf_sections().is_none());
+    }
+}
@@ -1,15 +1,26 @@
 #![allow(unused_imports)]
-use core::panic::PanicInfo;
+use core::{alloc::Layout, fmt::Write, panic::PanicInfo};
 
 use crate::terminal::vga::Color;
 
 #[cfg(not(test))]
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
+fn panic(info: &PanicInfo) -> ! {
     use crate::terminal::Terminal;
 
     let mut t = Terminal::default();
     t.write_color_str("Paniced!", Color::Error as u8);
+    if let Some(location) = info.location() {
+        let _ = write!(t, " at {}:{}", location.file(), location.line());
+    }
+    let _ = write!(t, "\n{}", info.message());
     t.flush();
     loop {}
 }
+
+/// Called when the kernel heap cannot serve an allocation, e.g. by `Box::new` or a growing `Vec`.
+#[cfg(not(test))]
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    panic!("memory allocation of {} bytes failed", layout.size());
+}
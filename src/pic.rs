@@ -0,0 +1,184 @@
+use crate::{idt, port};
+
+/// Vector of IRQ0 after remapping, right after the 32 CPU exceptions.
+pub const PIC_1_OFFSET: u8 = 32;
+
+/// Vector of IRQ8 after remapping.
+pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+
+/// Number of IRQ lines served by the master and slave PIC together.
+pub const IRQ_LINES: usize = 16;
+
+const PIC_1_COMMAND: u16 = 0x20;
+const PIC_1_DATA: u16 = 0x21;
+const PIC_2_COMMAND: u16 = 0xA0;
+const PIC_2_DATA: u16 = 0xA1;
+
+/// ICW1: start initialization, an ICW4 will follow.
+const ICW1_INIT: u8 = 0x11;
+/// ICW4: 8086/88 mode.
+const ICW4_8086: u8 = 0x01;
+/// OCW2: non-specific end of interrupt.
+const EOI: u8 = 0x20;
+/// OCW3: the next read of the command port returns the In-Service Register.
+const READ_ISR: u8 = 0x0B;
+
+/// The 16 legacy IRQ lines of the two cascaded 8259 PICs.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Irq {
+    Timer = 0,
+    Keyboard = 1,
+    Cascade = 2,
+    Com2 = 3,
+    Com1 = 4,
+    Lpt2 = 5,
+    Floppy = 6,
+    Lpt1 = 7,
+    Rtc = 8,
+    Acpi = 9,
+    Free10 = 10,
+    Free11 = 11,
+    Mouse = 12,
+    Fpu = 13,
+    PrimaryAta = 14,
+    SecondaryAta = 15,
+}
+
+impl Irq {
+    /// Returns the interrupt vector this line is delivered on after `remap`.
+    #[allow(dead_code)]
+    pub const fn vector(self) -> u8 {
+        PIC_1_OFFSET + self as u8
+    }
+}
+
+/// Reinitializes both PICs so IRQ0-15 are delivered on vectors `PIC_1_OFFSET..PIC_1_OFFSET + 16`
+/// instead of overlapping the CPU exceptions, then masks every line except the cascade.
+///
+/// Lines have to be enabled one by one with `unmask` once a handler is installed.
+///
+/// ## SAFETY
+/// Must run with interrupts disabled, and the IDT has to provide gates for the remapped vectors.
+pub unsafe fn remap() {
+    port::write(PIC_1_COMMAND, ICW1_INIT);
+    port::wait();
+    port::write(PIC_2_COMMAND, ICW1_INIT);
+    port::wait();
+
+    port::write(PIC_1_DATA, PIC_1_OFFSET);
+    port::wait();
+    port::write(PIC_2_DATA, PIC_2_OFFSET);
+    port::wait();
+
+    // ICW3: the slave sits on IRQ2 of the master (bit mask), and has cascade identity 2.
+    port::write(PIC_1_DATA, 1 << Irq::Cascade as u8);
+    port::wait();
+    port::write(PIC_2_DATA, Irq::Cascade as u8);
+    port::wait();
+
+    port::write(PIC_1_DATA, ICW4_8086);
+    port::wait();
+    port::write(PIC_2_DATA, ICW4_8086);
+    port::wait();
+
+    port::write(PIC_1_DATA, !(1 << Irq::Cascade as u8));
+    port::write(PIC_2_DATA, 0xFF);
+}
+
+/// Stops the PICs from delivering `irq`.
+#[allow(dead_code)]
+pub fn mask(irq: Irq) {
+    set_masked(irq, true);
+}
+
+/// Lets the PICs deliver `irq`.
+#[allow(dead_code)]
+pub fn unmask(irq: Irq) {
+    set_masked(irq, false);
+}
+
+/// Updates the bit of `irq` in the Interrupt Mask Register of its PIC. Interrupts are disabled in between
+/// the read and the write, so a handler changing the same register cannot have its update overwritten.
+fn set_masked(irq: Irq, masked: bool) {
+    let (port, bit) = data_port_and_bit(irq);
+
+    idt::without_interrupts(|| unsafe { port::write(port, with_line_masked(port::read(port), bit, masked)) });
+}
+
+/// Returns the mask register value `mask` with line `bit` masked or not.
+fn with_line_masked(mask: u8, bit: u8, masked: bool) -> u8 {
+    if masked {
+        mask | 1 << bit
+    } else {
+        mask & !(1 << bit)
+    }
+}
+
+/// Signals the end of the interrupt for `irq`. Lines of the slave PIC need an EOI on both chips,
+/// since the master saw them arrive on the cascade line.
+pub fn end_of_interrupt(irq: u8) {
+    unsafe {
+        if irq >= 8 {
+            port::write(PIC_2_COMMAND, EOI);
+        }
+        port::write(PIC_1_COMMAND, EOI);
+    }
+}
+
+/// Returns `true` if `irq` is a spurious interrupt that must not be handled nor acknowledged.
+///
+/// A PIC raises IRQ7 (master) or IRQ15 (slave) when a request disappears before it could be serviced.
+/// The two are told apart from real ones by their bit in the In-Service Register being clear.
+/// For a spurious IRQ15 the master still saw a real request on the cascade line, so it receives its EOI here.
+pub fn is_spurious(irq: u8) -> bool {
+    let (command, line) = match irq {
+        7 => (PIC_1_COMMAND, 7),
+        15 => (PIC_2_COMMAND, 7),
+        _ => return false,
+    };
+
+    let in_service = unsafe {
+        port::write(command, READ_ISR);
+        port::read(command)
+    };
+    if in_service & (1 << line) != 0 {
+        return false;
+    }
+
+    if irq == 15 {
+        unsafe { port::write(PIC_1_COMMAND, EOI) };
+    }
+    true
+}
+
+/// Returns the data port of the PIC serving `irq` and the bit of `irq` in its mask register.
+fn data_port_and_bit(irq: Irq) -> (u16, u8) {
+    match irq as u8 {
+        line @ 0..=7 => (PIC_1_DATA, line),
+        line => (PIC_2_DATA, line - 8),
+    }
+}
+
+#[cfg(test)]
+mod pic_test {
+    use super::*;
+
+    #[test]
+    fn test_lines_map_to_their_pic() {
+        assert_eq!(data_port_and_bit(Irq::Timer), (PIC_1_DATA, 0));
+        assert_eq!(data_port_and_bit(Irq::Lpt1), (PIC_1_DATA, 7));
+        assert_eq!(data_port_and_bit(Irq::Rtc), (PIC_2_DATA, 0));
+        assert_eq!(data_port_and_bit(Irq::Mouse), (PIC_2_DATA, 4));
+        assert_eq!(Irq::Mouse.vector(), 44);
+    }
+
+    #[test]
+    fn test_mask_only_changes_one_line() {
+        assert_eq!(with_line_masked(0b1111_1011, 1, false), 0b1111_1001);
+        assert_eq!(with_line_masked(0b1111_1001, 1, true), 0b1111_1011);
+        assert_eq!(with_line_masked(0b1111_1011, 2, true), 0b1111_1111);
+        assert_eq!(with_line_masked(0, 7, true), 0x80);
+    }
+}
@@ -0,0 +1,218 @@
+use core::{
+    ptr::{addr_of, addr_of_mut},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    idt::{self, irq, InterruptFrame},
+    pic::Irq,
+    port, stack,
+};
+
+/// Frequency of the oscillator driving the 8253/8254 counters, in Hz.
+pub const PIT_FREQUENCY: u32 = 1_193_182;
+
+/// Tick frequency used by the kernel unless configured otherwise, one tick per millisecond.
+pub const DEFAULT_FREQUENCY: u32 = 1000;
+
+const CHANNEL_0_DATA: u16 = 0x40;
+const COMMAND: u16 = 0x43;
+
+/// Command: channel 0, low then high byte of the reload value, mode 2 (rate generator), binary counting.
+const CHANNEL_0_RATE_GENERATOR: u8 = 0x34;
+/// Command: latch the current count of channel 0 so it can be read without tearing.
+const CHANNEL_0_LATCH: u8 = 0x00;
+
+/// Largest reload value, written as 0.
+const MAX_DIVISOR: u32 = 0x10000;
+
+/// Reload value channel 0 was programmed with, 0 until `init` runs.
+static DIVISOR: AtomicU32 = AtomicU32::new(0);
+
+/// Number of IRQ0 received since `init`. A `u64` cannot be accessed atomically on i386,
+/// it is only written by `timer_interrupt` and read with interrupts disabled.
+static mut TICKS: u64 = 0;
+
+/// Programs channel 0 to raise IRQ0 at `frequency` Hz and starts counting ticks.
+///
+/// ### Parameters:
+/// - `frequency`: The requested tick frequency, clamped to what the PIT can produce (19 Hz to 1.19 MHz).
+///   The actual frequency is `PIT_FREQUENCY / divisor` and returned by `frequency`.
+///
+/// ## SAFETY
+/// The IDT and PIC must be initialized. Must not be called from an interrupt handler.
+pub unsafe fn init(frequency: u32) {
+    let divisor = divisor_for(frequency);
+
+    idt::without_interrupts(|| {
+        port::write(COMMAND, CHANNEL_0_RATE_GENERATOR);
+        port::write(CHANNEL_0_DATA, divisor as u8);
+        port::write(CHANNEL_0_DATA, (divisor >> 8) as u8);
+    });
+    DIVISOR.store(divisor, Ordering::Release);
+
+    irq::set_handler(Irq::Timer, timer_interrupt);
+}
+
+/// Computes the reload value closest to `frequency`.
+fn divisor_for(frequency: u32) -> u32 {
+    let frequency = frequency.max(1);
+
+    ((PIT_FREQUENCY + frequency / 2) / frequency).clamp(1, MAX_DIVISOR)
+}
+
+/// IRQ0 handler: advances the tick counter and checks the stack canary, so that an overflow is
+/// reported while the kernel is still running instead of when it reaches the main loop again.
+fn timer_interrupt(_frame: &mut InterruptFrame) {
+    unsafe { *addr_of_mut!(TICKS) += 1 };
+    stack::check();
+}
+
+/// Returns `true` once `init` programmed the timer.
+pub fn is_running() -> bool {
+    DIVISOR.load(Ordering::Acquire) != 0
+}
+
+/// Returns the actual tick frequency in Hz, rounded down, or 0 if the timer is not running.
+pub fn frequency() -> u32 {
+    match DIVISOR.load(Ordering::Acquire) {
+        0 => 0,
+        divisor => PIT_FREQUENCY / divisor,
+    }
+}
+
+/// Returns the number of ticks since `init`. The counter is monotonic and never wraps in practice.
+pub fn ticks() -> u64 {
+    idt::without_interrupts(|| unsafe { *addr_of!(TICKS) })
+}
+
+/// Returns the time elapsed since `init`, in milliseconds.
+#[allow(dead_code)]
+pub fn uptime_ms() -> u64 {
+    ticks_to_ms(ticks(), DIVISOR.load(Ordering::Acquire))
+}
+
+fn ticks_to_ms(ticks: u64, divisor: u32) -> u64 {
+    ticks * divisor as u64 * 1000 / PIT_FREQUENCY as u64
+}
+
+/// Returns the number of ticks covering at least `ms` milliseconds.
+fn ms_to_ticks(ms: u64, divisor: u32) -> u64 {
+    (ms * PIT_FREQUENCY as u64).div_ceil(divisor as u64 * 1000)
+}
+
+/// Returns the tick count at which `ms` milliseconds will have elapsed, to be compared with `ticks`.
+///
+/// The deadline is one tick late rather than early, as the current tick may be about to end.
+pub fn deadline_ms(ms: u64) -> u64 {
+    ticks() + ms_to_ticks(ms, DIVISOR.load(Ordering::Acquire).max(1)) + 1
+}
+
+/// Sleeps for at least `ms` milliseconds, halting the CPU between ticks.
+///
+/// Falls back to `busy_wait_us` if the timer is not running yet.
+///
+/// ### Notes:
+/// - Interrupts are enabled on return, as they have to be for the ticks to advance.
+#[allow(dead_code)]
+pub fn sleep_ms(ms: u64) {
+    if !is_running() {
+        for _ in 0..ms {
+            busy_wait_us(1000);
+        }
+        return;
+    }
+
+    let deadline = deadline_ms(ms);
+    loop {
+        idt::disable_interrupts();
+        if unsafe { *addr_of!(TICKS) } >= deadline {
+            idt::enable_interrupts();
+            return;
+        }
+        idt::enable_interrupts_and_halt();
+    }
+}
+
+/// Spins for at least `us` microseconds, for delays shorter than a tick or while interrupts are disabled.
+///
+/// The elapsed time is measured on the counter of channel 0, or with ~1µs writes to port 0x80 if
+/// the timer is not running yet.
+#[allow(dead_code)]
+pub fn busy_wait_us(us: u32) {
+    let divisor = DIVISOR.load(Ordering::Acquire);
+    if divisor == 0 {
+        for _ in 0..us {
+            port::wait();
+        }
+        return;
+    }
+
+    let target = (us as u64 * PIT_FREQUENCY as u64).div_ceil(1_000_000);
+    let mut elapsed = 0;
+    let mut previous = read_count();
+    while elapsed < target {
+        let current = read_count();
+        elapsed += count_delta(previous, current, divisor) as u64;
+        previous = current;
+    }
+}
+
+/// Returns the number of PIT cycles between two reads of the counter, which counts down and reloads
+/// with `divisor` when it reaches 0.
+fn count_delta(previous: u32, current: u32, divisor: u32) -> u32 {
+    if current <= previous {
+        previous - current
+    } else {
+        previous + divisor - current
+    }
+}
+
+/// Reads the current count of channel 0.
+fn read_count() -> u32 {
+    let count = idt::without_interrupts(|| unsafe {
+        port::write(COMMAND, CHANNEL_0_LATCH);
+        let low = port::read(CHANNEL_0_DATA);
+        let high = port::read(CHANNEL_0_DATA);
+        u16::from_le_bytes([low, high])
+    });
+
+    // A reload value of 0 counts from 65536.
+    if count == 0 {
+        MAX_DIVISOR
+    } else {
+        count as u32
+    }
+}
+
+#[cfg(test)]
+mod pit_test {
+    use super::*;
+
+    #[test]
+    fn test_divisor_for() {
+        assert_eq!(divisor_for(1000), 1193);
+        assert_eq!(divisor_for(100), 11932);
+        assert_eq!(divisor_for(1), MAX_DIVISOR);
+        assert_eq!(divisor_for(0), MAX_DIVISOR);
+        assert_eq!(divisor_for(u32::MAX), 1);
+    }
+
+    #[test]
+    fn test_tick_conversions() {
+        let divisor = divisor_for(1000);
+
+        assert_eq!(ticks_to_ms(1000, divisor), 999);
+        assert_eq!(ticks_to_ms(1001, divisor), 1000);
+        assert_eq!(ms_to_ticks(0, divisor), 0);
+        assert_eq!(ms_to_ticks(10, divisor), 11);
+        assert_eq!(ms_to_ticks(10, divisor_for(100)), 1);
+    }
+
+    #[test]
+    fn test_count_delta_across_reload() {
+        assert_eq!(count_delta(1000, 400, 1193), 600);
+        assert_eq!(count_delta(10, 1190, 1193), 13);
+        assert_eq!(count_delta(5, 5, 1193), 0);
+    }
+}
@@ -0,0 +1,41 @@
+use core::arch::asm;
+
+/// An unused port that is safe to write to. Writing to it takes roughly one microsecond,
+/// which gives slow devices like the 8259 PIC time to process the previous command.
+const POST_CODE_PORT: u16 = 0x80;
+
+/// Reads a byte from the I/O `port`.
+///
+/// ## SAFETY
+/// Reading from a port can have side effects on the device behind it (e.g. popping a byte
+/// off the PS2 output buffer), the caller has to know what lives at `port`.
+pub unsafe fn read(port: u16) -> u8 {
+    let res: u8;
+
+    asm!(
+        "in al, dx",
+        in("dx") port,
+        out("al") res,
+        options(nomem, nostack, preserves_flags),
+    );
+
+    res
+}
+
+/// Writes `value` to the I/O `port`.
+///
+/// ## SAFETY
+/// Writing to a port reconfigures the device behind it, the caller has to know what lives at `port`.
+pub unsafe fn write(port: u16, value: u8) {
+    asm!(
+        "out dx, al",
+        in("dx") port,
+        in("al") value,
+        options(nomem, nostack, preserves_flags),
+    );
+}
+
+/// Waits roughly one microsecond by writing to an unused port.
+pub fn wait() {
+    unsafe { write(POST_CODE_PORT, 0) };
+}
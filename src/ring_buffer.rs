@@ -0,0 +1,124 @@
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A fixed-size, lock-free single-producer/single-consumer queue of bytes.
+///
+/// It is meant to hand bytes from an interrupt handler (the only producer) to the main loop
+/// (the only consumer) without disabling interrupts. `head` and `tail` are free-running counters,
+/// their difference is the number of queued bytes.
+///
+/// `N` has to be a power of two so the counters can wrap around `usize::MAX` without skipping slots.
+pub struct RingBuffer<const N: usize> {
+    buffer: UnsafeCell<[u8; N]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// SAFETY: The producer only writes the slot at `head` before publishing it, the consumer only reads
+// the slot at `tail` before releasing it, so the two never access the same slot concurrently.
+unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+
+impl<const N: usize> RingBuffer<N> {
+    const POWER_OF_TWO: () = assert!(N.is_power_of_two(), "RingBuffer size must be a power of two");
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::POWER_OF_TWO;
+
+        RingBuffer {
+            buffer: UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Appends `byte` to the queue. Must only be called by the producer.
+    ///
+    /// ### Returns:
+    /// - `true` if the byte was queued.
+    /// - `false` if the queue is full, in which case `byte` is dropped.
+    pub fn push(&self, byte: u8) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+
+        if head.wrapping_sub(tail) == N {
+            return false;
+        }
+
+        unsafe { (*self.buffer.get())[head % N] = byte };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Removes the oldest byte from the queue. Must only be called by the consumer.
+    pub fn pop(&self) -> Option<u8> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        let byte = unsafe { (*self.buffer.get())[tail % N] };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(byte)
+    }
+
+    /// Returns `true` if there is nothing to `pop`.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod ring_buffer_test {
+    use super::*;
+
+    #[test]
+    fn test_pop_returns_bytes_in_push_order() {
+        let r: RingBuffer<4> = RingBuffer::new();
+
+        assert!(r.push(1));
+        assert!(r.push(2));
+        assert!(r.push(3));
+
+        assert_eq!(r.pop(), Some(1));
+        assert_eq!(r.pop(), Some(2));
+        assert_eq!(r.pop(), Some(3));
+        assert_eq!(r.pop(), None);
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn test_push_drops_bytes_when_full() {
+        let r: RingBuffer<2> = RingBuffer::new();
+
+        assert!(r.push(1));
+        assert!(r.push(2));
+        assert!(!r.push(3));
+
+        assert_eq!(r.pop(), Some(1));
+        assert!(r.push(4));
+        assert_eq!(r.pop(), Some(2));
+        assert_eq!(r.pop(), Some(4));
+    }
+
+    #[test]
+    fn test_counters_wrap_around() {
+        let r: RingBuffer<4> = RingBuffer::new();
+        r.head.store(usize::MAX - 1, Ordering::Relaxed);
+        r.tail.store(usize::MAX - 1, Ordering::Relaxed);
+
+        for byte in 0..4 {
+            assert!(r.push(byte));
+        }
+        assert!(!r.push(4));
+
+        for byte in 0..4 {
+            assert_eq!(r.pop(), Some(byte));
+        }
+        assert!(r.is_empty());
+    }
+}
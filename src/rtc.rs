@@ -0,0 +1,370 @@
+use core::ptr::{addr_of, addr_of_mut};
+
+use crate::{
+    idt::{self, irq, InterruptFrame},
+    pic::Irq,
+    port,
+    terminal::Terminal,
+};
+
+const CMOS_INDEX: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+/// Set in the index written to `CMOS_INDEX` so no NMI interrupts an access. Cleared again once the access is done.
+const NMI_DISABLE: u8 = 1 << 7;
+
+const SECONDS: u8 = 0x00;
+const MINUTES: u8 = 0x02;
+const HOURS: u8 = 0x04;
+const DAY_OF_MONTH: u8 = 0x07;
+const MONTH: u8 = 0x08;
+const YEAR: u8 = 0x09;
+const STATUS_A: u8 = 0x0A;
+const STATUS_B: u8 = 0x0B;
+const STATUS_C: u8 = 0x0C;
+/// Left selected between accesses, as the BIOS does: it is read-only, so a stray write to `CMOS_DATA` is harmless.
+const STATUS_D: u8 = 0x0D;
+
+/// Status A: the clock is updating its registers, they may hold a mix of the old and new time.
+const UPDATE_IN_PROGRESS: u8 = 1 << 7;
+/// Status A: bits selecting the periodic interrupt rate.
+const RATE_MASK: u8 = 0x0F;
+/// Status B: raise IRQ8 at the rate selected in status A.
+const PERIODIC_INTERRUPT_ENABLE: u8 = 1 << 6;
+/// Status B: the registers hold binary values instead of BCD.
+const BINARY_MODE: u8 = 1 << 2;
+/// Status B: hours count from 0 to 23 instead of 1 to 12.
+const HOUR_24_MODE: u8 = 1 << 1;
+/// Hours register in 12h mode: the time is after noon.
+const HOUR_PM: u8 = 1 << 7;
+
+/// The RTC only stores two digits of the year. There is no reliable century register, so it is assumed.
+const CENTURY: u16 = 2000;
+
+/// Frequency of the RTC oscillator, divided by the periodic interrupt rate.
+const RTC_OSCILLATOR_FREQUENCY: u32 = 32_768;
+/// Fastest rate that works reliably, 8192 Hz. Rates 1 and 2 are rolled over to 256 and 128 Hz.
+pub const PERIODIC_RATE_FASTEST: u8 = 3;
+/// Slowest rate, 2 Hz.
+pub const PERIODIC_RATE_SLOWEST: u8 = 15;
+
+/// Number of IRQ8 received since `enable_periodic_interrupt`.
+static mut PERIODIC_TICKS: u64 = 0;
+
+/// A broken-down UTC date and time, as stored in the CMOS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    /// 1 to 12.
+    pub month: u8,
+    /// 1 to 31.
+    pub day: u8,
+    /// 0 to 23.
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Converts a Unix timestamp (seconds since 1970-01-01 00:00:00 UTC) to a broken-down date.
+    #[allow(dead_code)]
+    pub fn from_unix_timestamp(timestamp: u64) -> Self {
+        let days = timestamp / 86_400;
+        let seconds = timestamp % 86_400;
+        let (year, month, day) = civil_from_days(days);
+
+        DateTime {
+            year,
+            month,
+            day,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    /// Returns the number of seconds since 1970-01-01 00:00:00 UTC.
+    #[allow(dead_code)]
+    pub fn unix_timestamp(&self) -> u64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+
+        days * 86_400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64
+    }
+
+    /// Formats the date as `YYYY-MM-DD HH:MM:SS`.
+    pub fn format(&self) -> [u8; 19] {
+        let mut buf = *b"0000-00-00 00:00:00";
+
+        write_digits(&mut buf[0..4], self.year as u32);
+        write_digits(&mut buf[5..7], self.month as u32);
+        write_digits(&mut buf[8..10], self.day as u32);
+        write_digits(&mut buf[11..13], self.hour as u32);
+        write_digits(&mut buf[14..16], self.minute as u32);
+        write_digits(&mut buf[17..19], self.second as u32);
+        buf
+    }
+
+    /// Writes the date as `YYYY-MM-DD HH:MM:SS UTC` to the active screen of `t`.
+    pub fn write_to(&self, t: &mut Terminal) {
+        let buf = self.format();
+
+        // `format` only produces ASCII digits and separators.
+        t.write_str(core::str::from_utf8(&buf).unwrap());
+        t.write_str(" UTC");
+    }
+}
+
+/// Writes `value` in decimal into `buf`, zero-padded to its length.
+fn write_digits(buf: &mut [u8], mut value: u32) {
+    for digit in buf.iter_mut().rev() {
+        *digit = b'0' + (value % 10) as u8;
+        value /= 10;
+    }
+}
+
+/// Number of days between 1970-01-01 and the given date of the proleptic Gregorian calendar.
+///
+/// Years are shifted to start in March so the leap day is the last day of the year,
+/// see [chrono-Compatible Low-Level Date Algorithms](https://howardhinnant.github.io/date_algorithms.html).
+fn days_from_civil(year: u16, month: u8, day: u8) -> u64 {
+    let year = if month <= 2 { year as u64 - 1 } else { year as u64 };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let shifted_month = (month as u64 + 9) % 12;
+    let day_of_year = (153 * shifted_month + 2) / 5 + day as u64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`, returns the `(year, month, day)` that many days after 1970-01-01.
+fn civil_from_days(days: u64) -> (u16, u8, u8) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = era * 400 + year_of_era + (month <= 2) as u64;
+
+    (year as u16, month as u8, day as u8)
+}
+
+/// Raw content of the time registers, compared between two reads to detect an update in between.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Registers {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+}
+
+impl Registers {
+    /// Waits for the end of an update, then reads the time registers.
+    fn read() -> Self {
+        while read_register(STATUS_A) & UPDATE_IN_PROGRESS != 0 {
+            core::hint::spin_loop();
+        }
+
+        Registers {
+            second: read_register(SECONDS),
+            minute: read_register(MINUTES),
+            hour: read_register(HOURS),
+            day: read_register(DAY_OF_MONTH),
+            month: read_register(MONTH),
+            year: read_register(YEAR),
+        }
+    }
+
+    /// Converts the registers to a `DateTime`, following the data format of status B.
+    fn decode(&self, status_b: u8) -> DateTime {
+        let binary = status_b & BINARY_MODE != 0;
+        let value = |raw: u8| if binary { raw } else { bcd_to_binary(raw) };
+
+        let pm = self.hour & HOUR_PM != 0;
+        let mut hour = value(self.hour & !HOUR_PM);
+        if status_b & HOUR_24_MODE == 0 {
+            // 12 AM is midnight and 12 PM is noon.
+            hour %= 12;
+            if pm {
+                hour += 12;
+            }
+        }
+
+        DateTime {
+            year: CENTURY + value(self.year) as u16,
+            month: value(self.month),
+            day: value(self.day),
+            hour,
+            minute: value(self.minute),
+            second: value(self.second),
+        }
+    }
+}
+
+fn bcd_to_binary(bcd: u8) -> u8 {
+    (bcd >> 4) * 10 + (bcd & 0x0F)
+}
+
+/// Reads the current date and time from the CMOS real-time clock.
+///
+/// The registers are read until two reads in a row match, so an update that started
+/// right after the update-in-progress check cannot produce a torn time.
+pub fn now() -> DateTime {
+    let mut registers = Registers::read();
+    loop {
+        let again = Registers::read();
+        if again == registers {
+            break;
+        }
+        registers = again;
+    }
+
+    registers.decode(read_register(STATUS_B))
+}
+
+/// Blocks until the seconds register changes, right after the RTC ticked to the next second.
+///
+/// Used as a one second time base, e.g. to calibrate another clock before the PIT is programmed.
+pub fn wait_for_next_second() {
+    let second = Registers::read().second;
+    while Registers::read().second == second {
+        core::hint::spin_loop();
+    }
+}
+
+/// Makes the RTC raise IRQ8 periodically, as a tick source independent of the PIT.
+///
+/// ### Parameters:
+/// - `rate`: Between `PERIODIC_RATE_FASTEST` and `PERIODIC_RATE_SLOWEST`, clamped otherwise.
+///   The interrupt frequency is `32768 >> (rate - 1)` Hz.
+///
+/// ## SAFETY
+/// The IDT and PIC must be initialized. Must not be called from an interrupt handler.
+#[allow(dead_code)]
+pub unsafe fn enable_periodic_interrupt(rate: u8) {
+    let rate = rate.clamp(PERIODIC_RATE_FASTEST, PERIODIC_RATE_SLOWEST);
+
+    idt::without_interrupts(|| {
+        let status_a = read_register(STATUS_A);
+        write_register(STATUS_A, (status_a & !RATE_MASK) | rate);
+        let status_b = read_register(STATUS_B);
+        write_register(STATUS_B, status_b | PERIODIC_INTERRUPT_ENABLE);
+        // Throw away a pending interrupt, the RTC does not raise another one before status C is read.
+        read_register(STATUS_C);
+    });
+
+    irq::set_handler(Irq::Rtc, periodic_interrupt);
+}
+
+/// Returns the frequency of the periodic interrupt for `rate`, in Hz.
+#[allow(dead_code)]
+pub fn periodic_frequency(rate: u8) -> u32 {
+    RTC_OSCILLATOR_FREQUENCY >> (rate.clamp(PERIODIC_RATE_FASTEST, PERIODIC_RATE_SLOWEST) - 1)
+}
+
+/// Returns the number of periodic interrupts since `enable_periodic_interrupt`.
+#[allow(dead_code)]
+pub fn periodic_ticks() -> u64 {
+    idt::without_interrupts(|| unsafe { *addr_of!(PERIODIC_TICKS) })
+}
+
+/// IRQ8 handler: counts the tick and reads status C, or the RTC never raises the interrupt again.
+fn periodic_interrupt(_frame: &mut InterruptFrame) {
+    read_register(STATUS_C);
+    unsafe { *addr_of_mut!(PERIODIC_TICKS) += 1 };
+}
+
+/// Reads a CMOS register.
+///
+/// ### Notes:
+/// - Selecting the register and reading it must not be split by an interrupt that accesses the CMOS
+///   too, like `periodic_interrupt`, so both run with interrupts disabled.
+fn read_register(register: u8) -> u8 {
+    idt::without_interrupts(|| unsafe {
+        port::write(CMOS_INDEX, NMI_DISABLE | register);
+        let value = port::read(CMOS_DATA);
+        port::write(CMOS_INDEX, STATUS_D);
+        value
+    })
+}
+
+/// Writes a CMOS register. See `read_register`.
+fn write_register(register: u8, value: u8) {
+    idt::without_interrupts(|| unsafe {
+        port::write(CMOS_INDEX, NMI_DISABLE | register);
+        port::write(CMOS_DATA, value);
+        port::write(CMOS_INDEX, STATUS_D);
+    })
+}
+
+#[cfg(test)]
+mod rtc_test {
+    use super::*;
+
+    const DATE: DateTime = DateTime {
+        year: 2024,
+        month: 2,
+        day: 29,
+        hour: 13,
+        minute: 5,
+        second: 9,
+    };
+
+    #[test]
+    fn test_unix_timestamp() {
+        assert_eq!(DateTime::from_unix_timestamp(0).format(), *b"1970-01-01 00:00:00");
+        assert_eq!(DATE.unix_timestamp(), 1_709_211_909);
+        assert_eq!(DateTime::from_unix_timestamp(1_709_211_909), DATE);
+        assert_eq!(DateTime::from_unix_timestamp(951_782_400).format(), *b"2000-02-29 00:00:00");
+    }
+
+    #[test]
+    fn test_format_pads_with_zeros() {
+        assert_eq!(DATE.format(), *b"2024-02-29 13:05:09");
+    }
+
+    #[test]
+    fn test_decode_bcd_12h() {
+        let registers = Registers {
+            second: 0x09,
+            minute: 0x05,
+            hour: HOUR_PM | 0x01,
+            day: 0x29,
+            month: 0x02,
+            year: 0x24,
+        };
+        assert_eq!(registers.decode(0), DATE);
+
+        let midnight = Registers { hour: 0x12, ..registers };
+        assert_eq!(midnight.decode(0).hour, 0);
+        let noon = Registers {
+            hour: HOUR_PM | 0x12,
+            ..registers
+        };
+        assert_eq!(noon.decode(0).hour, 12);
+    }
+
+    #[test]
+    fn test_decode_binary_24h() {
+        let registers = Registers {
+            second: 9,
+            minute: 5,
+            hour: 13,
+            day: 29,
+            month: 2,
+            year: 24,
+        };
+        assert_eq!(registers.decode(BINARY_MODE | HOUR_24_MODE), DATE);
+    }
+
+    #[test]
+    fn test_periodic_frequency() {
+        assert_eq!(periodic_frequency(6), 1024);
+        assert_eq!(periodic_frequency(PERIODIC_RATE_SLOWEST), 2);
+        assert_eq!(periodic_frequency(1), 8192);
+    }
+}
@@ -0,0 +1,97 @@
+use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+
+use crate::{
+    idt,
+    print::{slice_to_str, u64_to_base},
+    terminal::{vga::Color, Terminal},
+};
+
+/// Written over the lowest bytes of the stack by `init_canary`. The stack only reaches them shortly
+/// before running into the guard page, so a changed word means it nearly or actually overflowed.
+const CANARY: u32 = 0x57AC_CA11;
+/// Number of canary words, a deep frame that skips a few of them still lands on the others.
+const CANARY_WORDS: usize = 64;
+
+extern "C" {
+    /// Page right below the stack, left unmapped by `paging::init`. Defined in `assets/boot.s`.
+    static mut stack_guard: u8;
+    /// Lowest address of the stack, `KERNEL_STACK_SIZE` bytes below `stack_top`.
+    static mut stack_bottom: u8;
+    static stack_top: u8;
+}
+
+/// Returns the size of the kernel stack in bytes, `KERNEL_STACK_SIZE` in `assets/boot.s`.
+pub fn size() -> usize {
+    addr_of!(stack_top) as usize - addr_of!(stack_bottom) as usize
+}
+
+/// Returns `true` if `address` is in the guard page below the stack.
+pub fn is_guard_page(address: usize) -> bool {
+    (addr_of!(stack_guard) as usize..addr_of!(stack_bottom) as usize).contains(&address)
+}
+
+/// Fills the bottom of the stack with `CANARY`.
+///
+/// ## SAFETY
+/// Must run while the stack is far from full, before anything could have used its lowest bytes.
+pub unsafe fn init_canary() {
+    let words = addr_of_mut!(stack_bottom) as *mut u32;
+
+    for index in 0..CANARY_WORDS {
+        write_volatile(words.add(index), CANARY);
+    }
+}
+
+/// Returns `true` if every canary word is intact, `init_canary` must have run.
+pub fn is_canary_intact() -> bool {
+    let words = addr_of!(stack_bottom) as *const u32;
+
+    (0..CANARY_WORDS).all(|index| unsafe { read_volatile(words.add(index)) } == CANARY)
+}
+
+/// Reports a stack overflow if the canary was overwritten. Called by the main loop and on every timer tick.
+pub fn check() {
+    if !is_canary_intact() {
+        overflow("the canary at the bottom of the stack was overwritten", None);
+    }
+}
+
+/// Prints a "kernel stack overflow" report and halts the CPU.
+///
+/// ### Parameters:
+/// - `reason`: How the overflow was detected.
+/// - `address`: The address that was accessed below the stack, if known.
+///
+/// ### Notes:
+/// - The report needs a few KiB of stack, it must not run on a stack that is still full.
+pub fn overflow(reason: &str, address: Option<usize>) -> ! {
+    let mut t = Terminal::default();
+
+    write_overflow(&mut t, reason, address);
+    t.flush();
+
+    idt::halt()
+}
+
+/// Writes the report printed by `overflow` to `t`, without flushing it.
+pub fn write_overflow(t: &mut Terminal, reason: &str, address: Option<usize>) {
+    t.write_color_str("kernel stack overflow\n", Color::Error as u8);
+    t.write_str(reason);
+    t.write_str("\n");
+    if let Some(address) = address {
+        write_number(t, "address: 0x", address, 16);
+        t.write_str("\n");
+    }
+    write_number(t, "stack: 0x", addr_of!(stack_bottom) as usize, 16);
+    write_number(t, "-0x", addr_of!(stack_top) as usize, 16);
+    write_number(t, ", ", size() / 1024, 10);
+    t.write_str(" KiB, build with a larger STACK_SIZE\n");
+}
+
+/// Writes `prefix` followed by `value` in `base`.
+fn write_number(t: &mut Terminal, prefix: &str, value: usize, base: u8) {
+    let (slice, len) = u64_to_base(value as u64, base).unwrap();
+
+    t.write_str(prefix);
+    t.write_str(slice_to_str((&slice, len)).unwrap());
+}
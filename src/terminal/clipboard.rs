@@ -0,0 +1,52 @@
+use super::screen::BUFFER_SIZE;
+
+/// Kernel clipboard shared by the screens of a `Terminal`.
+///
+/// Only the characters are kept, pasted text takes the default color of the screen it lands on.
+pub struct Clipboard {
+    characters: [u8; BUFFER_SIZE],
+    len: usize,
+}
+
+impl Clipboard {
+    pub const fn new() -> Self {
+        Clipboard {
+            characters: [0; BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Replaces the content of the clipboard with the characters of `entries`.
+    ///
+    /// ### Parameters:
+    /// - `entries`: Screen buffer entries, the color in their upper byte is dropped.
+    pub fn copy(&mut self, entries: &[u16]) {
+        self.len = entries.len().min(BUFFER_SIZE);
+        for (dst, &entry) in self.characters.iter_mut().zip(&entries[..self.len]) {
+            *dst = (entry & 0xFF) as u8;
+        }
+    }
+
+    /// Returns the characters copied last, empty if nothing was copied yet.
+    pub fn contents(&self) -> &[u8] {
+        &self.characters[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod clipboard_test {
+    use super::*;
+    use crate::terminal::vga::Entry;
+
+    #[test]
+    fn test_copy_keeps_characters_only() {
+        let mut clipboard = Clipboard::new();
+        assert!(clipboard.contents().is_empty());
+
+        clipboard.copy(&[Entry::new(b'h').to_u16(), Entry::new_with_color(b'i', 0x4F).to_u16()]);
+        assert_eq!(clipboard.contents(), b"hi");
+
+        clipboard.copy(&[Entry::new(b'!').to_u16()]);
+        assert_eq!(clipboard.contents(), b"!");
+    }
+}
@@ -0,0 +1,519 @@
+use super::{
+    layout::{is_cased_letter, DeadKey, KeyboardLayout, Level, Symbol},
+    ps2::{
+        self,
+        command::{self, Leds},
+        controller::ControllerError,
+        extended_scancode_to_key, Key, SCANCODE_TO_KEY,
+    },
+};
+
+/// Bit set in the scancode of a key release (break code) in scancode set 1.
+const BREAK_BIT: u8 = 0x80;
+
+/// Prefix of the two-byte scancodes of extended keys (arrows, Home, right Ctrl, ...).
+const EXTENDED_PREFIX: u8 = 0xE0;
+
+/// First byte of the six-byte Pause sequence `E1 1D 45 E1 9D C5`.
+const PAUSE_PREFIX: u8 = 0xE1;
+
+/// Number of bytes following `PAUSE_PREFIX` in the Pause sequence.
+const PAUSE_SEQUENCE_REMAINING: u8 = 5;
+
+/// State of the modifier keys at the time of a `KeyEvent`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// Right Alt on layouts where it selects the third level of the keys.
+    pub alt_gr: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+/// A decoded key press or release.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers,
+    /// `true` for a press (or typematic repeat), `false` for a release.
+    pub pressed: bool,
+    /// The code page 437 character typed by this press in the active layout, if any.
+    /// Always `None` for releases and while Ctrl or Alt is held.
+    pub character: Option<u8>,
+    /// The accent of a pending dead key that could not be combined with `character`.
+    /// It has to be written before `character`.
+    pub accent: Option<u8>,
+}
+
+/// Position of the decoder inside a multi-byte scancode sequence.
+#[derive(Clone, Copy, PartialEq)]
+enum Sequence {
+    /// The next byte starts a new scancode.
+    Start,
+    /// An `EXTENDED_PREFIX` was received, the next byte selects the extended key.
+    Extended,
+    /// A `PAUSE_PREFIX` was received and this many bytes of the Pause sequence are left.
+    Pause(u8),
+}
+
+/// Stateful scancode set 1 decoder that tracks the modifier keys across presses and releases
+/// and reassembles the multi-byte sequences of extended keys.
+pub struct Keyboard {
+    sequence: Sequence,
+    left_shift: bool,
+    right_shift: bool,
+    left_ctrl: bool,
+    right_ctrl: bool,
+    left_alt: bool,
+    right_alt: bool,
+    caps_lock: bool,
+    num_lock: bool,
+    scroll_lock: bool,
+    /// Set when a lock key toggled and the LEDs do not reflect it yet.
+    leds_outdated: bool,
+    layout: KeyboardLayout,
+    dead_key: Option<DeadKey>,
+}
+
+impl Keyboard {
+    pub const fn new() -> Self {
+        Keyboard {
+            sequence: Sequence::Start,
+            left_shift: false,
+            right_shift: false,
+            left_ctrl: false,
+            right_ctrl: false,
+            left_alt: false,
+            right_alt: false,
+            caps_lock: false,
+            num_lock: false,
+            scroll_lock: false,
+            leds_outdated: false,
+            layout: KeyboardLayout::Us,
+            dead_key: None,
+        }
+    }
+
+    /// Returns the active keyboard layout.
+    #[allow(dead_code)]
+    pub fn layout(&self) -> KeyboardLayout {
+        self.layout
+    }
+
+    /// Switches to `layout`, discarding a pending dead key.
+    pub fn set_layout(&mut self, layout: KeyboardLayout) {
+        self.layout = layout;
+        self.dead_key = None;
+    }
+
+    /// Returns the current state of the modifier keys.
+    pub fn modifiers(&self) -> Modifiers {
+        let alt_gr = self.right_alt && self.layout.has_alt_gr();
+
+        Modifiers {
+            shift: self.left_shift || self.right_shift,
+            ctrl: self.left_ctrl || self.right_ctrl,
+            alt: self.left_alt || (self.right_alt && !alt_gr),
+            alt_gr,
+            caps_lock: self.caps_lock,
+            num_lock: self.num_lock,
+            scroll_lock: self.scroll_lock,
+        }
+    }
+
+    /// Returns the LED state matching the lock keys.
+    pub fn leds(&self) -> Leds {
+        Leds {
+            scroll_lock: self.scroll_lock,
+            num_lock: self.num_lock,
+            caps_lock: self.caps_lock,
+        }
+    }
+
+    /// Sends the state of the lock keys to the keyboard LEDs.
+    pub fn sync_leds(&mut self) -> Result<(), ControllerError> {
+        self.leds_outdated = false;
+        command::set_leds(self.leds())
+    }
+
+    /// Feeds a single byte of a scancode to the decoder.
+    ///
+    /// ### Returns:
+    /// - `Some(KeyEvent)` if `scancode` completes the make or break code of a supported key.
+    /// - `None` for prefixes, bytes in the middle of a sequence and unsupported scancodes.
+    ///
+    /// ### Notes:
+    /// - The Pause key has no break code, its sequence yields a single press event.
+    /// - While Num Lock is off, the keypad digits and dot are reported as the navigation keys printed on them.
+    /// - Ctrl + Alt + L switches to the next keyboard layout.
+    pub fn decode(&mut self, scancode: u8) -> Option<KeyEvent> {
+        let extended = match (self.sequence, scancode) {
+            (Sequence::Pause(1), _) => {
+                self.sequence = Sequence::Start;
+                return Some(self.event(Key::Pause, true));
+            }
+            (Sequence::Pause(remaining), _) => {
+                self.sequence = Sequence::Pause(remaining - 1);
+                return None;
+            }
+            (Sequence::Start, EXTENDED_PREFIX) => {
+                self.sequence = Sequence::Extended;
+                return None;
+            }
+            (Sequence::Start, PAUSE_PREFIX) => {
+                self.sequence = Sequence::Pause(PAUSE_SEQUENCE_REMAINING);
+                return None;
+            }
+            (Sequence::Start, _) => false,
+            (Sequence::Extended, _) => {
+                self.sequence = Sequence::Start;
+                true
+            }
+        };
+
+        let pressed = scancode & BREAK_BIT == 0;
+        let code = scancode & !BREAK_BIT;
+        let mut key = if extended {
+            extended_scancode_to_key(code)?
+        } else {
+            SCANCODE_TO_KEY[code as usize]?
+        };
+
+        match key {
+            Key::LeftShift => self.left_shift = pressed,
+            Key::RightShift => self.right_shift = pressed,
+            Key::LeftCtrl => self.left_ctrl = pressed,
+            Key::RightCtrl => self.right_ctrl = pressed,
+            Key::LeftAlt => self.left_alt = pressed,
+            Key::RightAlt => self.right_alt = pressed,
+            Key::CapsLock if pressed => self.caps_lock = !self.caps_lock,
+            Key::NumLock if pressed => self.num_lock = !self.num_lock,
+            Key::ScrollLock if pressed => self.scroll_lock = !self.scroll_lock,
+            _ => {}
+        }
+        if pressed && matches!(key, Key::CapsLock | Key::NumLock | Key::ScrollLock) {
+            self.leds_outdated = true;
+        }
+
+        if !self.num_lock {
+            key = key.keypad_navigation().unwrap_or(key);
+        }
+
+        let modifiers = self.modifiers();
+        if pressed && key == Key::L && modifiers.ctrl && modifiers.alt {
+            self.set_layout(self.layout.next());
+        }
+
+        Some(self.event(key, pressed))
+    }
+
+    fn event(&mut self, key: Key, pressed: bool) -> KeyEvent {
+        let modifiers = self.modifiers();
+        let mut event = KeyEvent {
+            key,
+            modifiers,
+            pressed,
+            character: None,
+            accent: None,
+        };
+
+        if !pressed || modifiers.ctrl || modifiers.alt {
+            return event;
+        }
+
+        let level = if modifiers.alt_gr {
+            Level::AltGr
+        } else {
+            let caps = match self.layout.symbol(key, Level::Base) {
+                Some(Symbol::Char(c)) => modifiers.caps_lock && is_cased_letter(c),
+                _ => false,
+            };
+            if modifiers.shift != caps {
+                Level::Shift
+            } else {
+                Level::Base
+            }
+        };
+
+        match (self.layout.symbol(key, level), self.dead_key.take()) {
+            (None, pending) => self.dead_key = pending,
+            (Some(Symbol::Dead(dead_key)), None) => self.dead_key = Some(dead_key),
+            (Some(Symbol::Dead(dead_key)), Some(pending)) => {
+                event.character = Some(pending.spacing());
+                self.dead_key = Some(dead_key);
+            }
+            (Some(Symbol::Char(c)), None) => event.character = Some(c),
+            (Some(Symbol::Char(b' ')), Some(pending)) => event.character = Some(pending.spacing()),
+            (Some(Symbol::Char(c)), Some(pending)) => match pending.compose(c) {
+                Some(composed) => event.character = Some(composed),
+                None => {
+                    event.accent = Some(pending.spacing());
+                    event.character = Some(c);
+                }
+            },
+        }
+
+        event
+    }
+
+    /// Decodes the scancodes queued by the keyboard interrupt until one yields an event.
+    ///
+    /// ### Returns:
+    /// - `Some(KeyEvent)` for the oldest supported key press or release.
+    /// - `None` once the queue is drained.
+    pub fn read_buffered(&mut self) -> Option<KeyEvent> {
+        while let Some(code) = ps2::read_scancode() {
+            if let Some(event) = self.decode_and_sync(code) {
+                return Some(event);
+            }
+        }
+
+        None
+    }
+
+    /// Decodes a scancode read from the keyboard and updates its LEDs if a lock key toggled.
+    /// An LED update that fails is not retried, the LEDs catch up with the next lock key press.
+    fn decode_and_sync(&mut self, scancode: u8) -> Option<KeyEvent> {
+        let event = self.decode(scancode);
+
+        if self.leds_outdated {
+            let _ = self.sync_leds();
+        }
+
+        event
+    }
+}
+
+#[cfg(test)]
+mod keyboard_test {
+    use super::*;
+
+    const LEFT_SHIFT: u8 = 0x2A;
+    const RIGHT_SHIFT: u8 = 0x36;
+    const CAPS_LOCK: u8 = 0x3A;
+    const LEFT_CTRL: u8 = 0x1D;
+    const A: u8 = 0x1E;
+    const N1: u8 = 0x02;
+    const SEMICOLON: u8 = 0x27;
+    const NUM_LOCK: u8 = 0x45;
+    const KEYPAD_8: u8 = 0x48;
+
+    fn type_key(k: &mut Keyboard, scancode: u8) -> Option<u8> {
+        let event = k.decode(scancode).unwrap();
+        k.decode(scancode | BREAK_BIT);
+        event.character
+    }
+
+    #[test]
+    fn test_unmodified_keys_are_lowercase() {
+        let mut k = Keyboard::new();
+
+        assert_eq!(type_key(&mut k, A), Some(b'a'));
+        assert_eq!(type_key(&mut k, N1), Some(b'1'));
+    }
+
+    #[test]
+    fn test_shift_produces_us_symbols() {
+        let mut k = Keyboard::new();
+
+        k.decode(LEFT_SHIFT);
+        assert_eq!(type_key(&mut k, A), Some(b'A'));
+        assert_eq!(type_key(&mut k, N1), Some(b'!'));
+        assert_eq!(type_key(&mut k, SEMICOLON), Some(b':'));
+        k.decode(LEFT_SHIFT | BREAK_BIT);
+
+        assert_eq!(type_key(&mut k, A), Some(b'a'));
+    }
+
+    #[test]
+    fn test_shift_stays_held_until_both_are_released() {
+        let mut k = Keyboard::new();
+
+        k.decode(LEFT_SHIFT);
+        k.decode(RIGHT_SHIFT);
+        k.decode(LEFT_SHIFT | BREAK_BIT);
+        assert_eq!(type_key(&mut k, A), Some(b'A'));
+
+        k.decode(RIGHT_SHIFT | BREAK_BIT);
+        assert_eq!(type_key(&mut k, A), Some(b'a'));
+    }
+
+    #[test]
+    fn test_caps_lock_toggles_letters_only() {
+        let mut k = Keyboard::new();
+
+        type_key(&mut k, CAPS_LOCK);
+        assert_eq!(type_key(&mut k, A), Some(b'A'));
+        assert_eq!(type_key(&mut k, N1), Some(b'1'));
+
+        k.decode(LEFT_SHIFT);
+        assert_eq!(type_key(&mut k, A), Some(b'a'));
+        assert_eq!(type_key(&mut k, N1), Some(b'!'));
+        k.decode(LEFT_SHIFT | BREAK_BIT);
+
+        type_key(&mut k, CAPS_LOCK);
+        assert_eq!(type_key(&mut k, A), Some(b'a'));
+    }
+
+    #[test]
+    fn test_release_events_and_ctrl_suppress_characters() {
+        let mut k = Keyboard::new();
+
+        let release = k.decode(A | BREAK_BIT).unwrap();
+        assert!(!release.pressed);
+        assert_eq!(release.key, Key::A);
+
+        k.decode(LEFT_CTRL);
+        let event = k.decode(A).unwrap();
+        assert!(event.modifiers.ctrl);
+        assert_eq!(event.character, None);
+    }
+
+    #[test]
+    fn test_extended_arrows_differ_from_keypad() {
+        let mut k = Keyboard::new();
+        type_key(&mut k, NUM_LOCK);
+
+        assert_eq!(k.decode(KEYPAD_8).unwrap().key, Key::Keypad8);
+        assert_eq!(k.decode(EXTENDED_PREFIX), None);
+        assert_eq!(k.decode(KEYPAD_8).unwrap().key, Key::ArrowUp);
+
+        assert_eq!(k.decode(EXTENDED_PREFIX), None);
+        let release = k.decode(KEYPAD_8 | BREAK_BIT).unwrap();
+        assert_eq!(release.key, Key::ArrowUp);
+        assert!(!release.pressed);
+    }
+
+    #[test]
+    fn test_keypad_follows_num_lock() {
+        let mut k = Keyboard::new();
+
+        assert_eq!(k.decode(KEYPAD_8).unwrap().key, Key::ArrowUp);
+
+        type_key(&mut k, NUM_LOCK);
+        k.decode(LEFT_SHIFT);
+        assert_eq!(type_key(&mut k, KEYPAD_8), Some(b'8'));
+    }
+
+    #[test]
+    fn test_right_ctrl_is_a_modifier() {
+        let mut k = Keyboard::new();
+
+        assert_eq!(k.decode(EXTENDED_PREFIX), None);
+        assert_eq!(k.decode(LEFT_CTRL).unwrap().key, Key::RightCtrl);
+        assert!(k.decode(A).unwrap().modifiers.ctrl);
+    }
+
+    #[test]
+    fn test_print_screen_sequence_skips_fake_shift() {
+        let mut k = Keyboard::new();
+        let make = [0xE0, 0x2A, 0xE0, 0x37];
+        let break_ = [0xE0, 0xB7, 0xE0, 0xAA];
+
+        let events: [Option<KeyEvent>; 4] = make.map(|code| k.decode(code));
+        assert_eq!(events[..3], [None, None, None]);
+        assert_eq!(events[3].unwrap().key, Key::PrintScreen);
+        assert!(events[3].unwrap().pressed);
+        assert!(!k.modifiers().shift);
+
+        let events: [Option<KeyEvent>; 4] = break_.map(|code| k.decode(code));
+        assert_eq!(events[1].unwrap().key, Key::PrintScreen);
+        assert!(!events[1].unwrap().pressed);
+        assert_eq!(events[3], None);
+    }
+
+    #[test]
+    fn test_pause_sequence_yields_a_single_press() {
+        let mut k = Keyboard::new();
+        let pause = [0xE1, 0x1D, 0x45, 0xE1, 0x9D, 0xC5];
+
+        let events: [Option<KeyEvent>; 6] = pause.map(|code| k.decode(code));
+        assert_eq!(events[..5], [None, None, None, None, None]);
+        assert_eq!(events[5].unwrap().key, Key::Pause);
+        assert!(!k.modifiers().ctrl);
+        assert!(!k.modifiers().num_lock);
+
+        assert_eq!(k.decode(A).unwrap().key, Key::A);
+    }
+
+    #[test]
+    fn test_lock_keys_mark_leds_outdated() {
+        let mut k = Keyboard::new();
+        const SCROLL_LOCK: u8 = 0x46;
+
+        type_key(&mut k, CAPS_LOCK);
+        type_key(&mut k, SCROLL_LOCK);
+
+        assert!(k.leds_outdated);
+        assert_eq!(
+            k.leds(),
+            Leds {
+                scroll_lock: true,
+                num_lock: false,
+                caps_lock: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_ctrl_alt_l_cycles_layouts() {
+        let mut k = Keyboard::new();
+        const Q: u8 = 0x10;
+        const LEFT_ALT: u8 = 0x38;
+        const L: u8 = 0x26;
+
+        k.decode(LEFT_CTRL);
+        k.decode(LEFT_ALT);
+        type_key(&mut k, L);
+        k.decode(LEFT_ALT | BREAK_BIT);
+        k.decode(LEFT_CTRL | BREAK_BIT);
+
+        assert_eq!(k.layout(), KeyboardLayout::French);
+        assert_eq!(type_key(&mut k, Q), Some(b'a'));
+    }
+
+    #[test]
+    fn test_alt_gr_selects_third_level() {
+        let mut k = Keyboard::new();
+        k.set_layout(KeyboardLayout::French);
+        const ZERO: u8 = 0x0B;
+        const ALT: u8 = 0x38;
+
+        k.decode(EXTENDED_PREFIX);
+        k.decode(ALT);
+        assert_eq!(type_key(&mut k, ZERO), Some(b'@'));
+    }
+
+    #[test]
+    fn test_dead_keys_combine_with_next_character() {
+        let mut k = Keyboard::new();
+        k.set_layout(KeyboardLayout::French);
+        const CIRCUMFLEX: u8 = 0x1A;
+        const E: u8 = 0x12;
+        const X: u8 = 0x2D;
+        const SPACE: u8 = 0x39;
+
+        assert_eq!(type_key(&mut k, CIRCUMFLEX), None);
+        assert_eq!(type_key(&mut k, E), Some(0x88));
+
+        type_key(&mut k, CIRCUMFLEX);
+        let event = k.decode(X).unwrap();
+        assert_eq!((event.accent, event.character), (Some(b'^'), Some(b'x')));
+
+        type_key(&mut k, CIRCUMFLEX);
+        assert_eq!(type_key(&mut k, SPACE), Some(b'^'));
+    }
+
+    #[test]
+    fn test_caps_lock_uppercases_umlauts() {
+        let mut k = Keyboard::new();
+        k.set_layout(KeyboardLayout::German);
+        const SEMICOLON_KEY: u8 = 0x27;
+
+        type_key(&mut k, CAPS_LOCK);
+        assert_eq!(type_key(&mut k, SEMICOLON_KEY), Some(0x99));
+    }
+}
@@ -0,0 +1,347 @@
+use super::ps2::Key::{self, *};
+
+/// Shift state used to look up the symbol of a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Base,
+    Shift,
+    /// The right Alt key on layouts that use it as AltGr.
+    AltGr,
+}
+
+/// An accent key that produces no character on its own but modifies the next one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadKey {
+    Circumflex,
+    Diaeresis,
+    Acute,
+    Grave,
+}
+
+/// What a key produces at a given `Level`. Characters are [code page 437](https://en.wikipedia.org/wiki/Code_page_437)
+/// bytes, the character set of the VGA text mode, so they can be written to a `Screen` as they are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symbol {
+    Char(u8),
+    Dead(DeadKey),
+}
+
+use Level::*;
+use Symbol::*;
+
+/// A keyboard layout, mapping the physical keys (named after their US legend) to symbols.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyboardLayout {
+    /// US QWERTY.
+    Us,
+    /// French AZERTY.
+    French,
+    /// German QWERTZ.
+    German,
+}
+
+impl KeyboardLayout {
+    /// Every supported layout, in the order `next` cycles through them.
+    pub const ALL: [KeyboardLayout; 3] = [KeyboardLayout::Us, KeyboardLayout::French, KeyboardLayout::German];
+
+    /// Returns the short name of the layout, as accepted by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyboardLayout::Us => "us",
+            KeyboardLayout::French => "fr",
+            KeyboardLayout::German => "de",
+        }
+    }
+
+    /// Looks up a layout by its short name (`us`, `fr` or `de`).
+    pub fn from_name(name: &str) -> Option<KeyboardLayout> {
+        Self::ALL.into_iter().find(|layout| layout.name() == name)
+    }
+
+    /// Returns the layout following this one in `ALL`, wrapping around.
+    pub fn next(self) -> KeyboardLayout {
+        let index = Self::ALL.iter().position(|&layout| layout == self).unwrap_or(0);
+
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Returns `true` if the right Alt key acts as AltGr instead of a second Alt key.
+    pub fn has_alt_gr(self) -> bool {
+        self != KeyboardLayout::Us
+    }
+
+    /// Returns the symbol `key` produces at `level`, or `None` if it produces nothing.
+    pub fn symbol(self, key: Key, level: Level) -> Option<Symbol> {
+        match self {
+            KeyboardLayout::Us => us(key, level),
+            KeyboardLayout::French => french(key, level),
+            KeyboardLayout::German => german(key, level),
+        }
+    }
+}
+
+/// Returns `true` if Caps Lock should turn `c` into its Shift level symbol.
+pub fn is_cased_letter(c: u8) -> bool {
+    // ü, é, ä and ö: the lowercase letters of the supported layouts with an uppercase form in code page 437.
+    c.is_ascii_alphabetic() || matches!(c, 0x81 | 0x82 | 0x84 | 0x94)
+}
+
+impl DeadKey {
+    /// Returns the accent as a standalone character, typed when the dead key is followed by a space
+    /// or by a character it cannot be combined with.
+    pub fn spacing(self) -> u8 {
+        match self {
+            DeadKey::Circumflex => b'^',
+            DeadKey::Diaeresis => b'"',
+            DeadKey::Acute => b'\'',
+            DeadKey::Grave => b'`',
+        }
+    }
+
+    /// Combines the accent with `c`.
+    ///
+    /// ### Returns:
+    /// - `Some(u8)` with the accented code page 437 character.
+    /// - `None` if code page 437 has no accented form of `c`.
+    pub fn compose(self, c: u8) -> Option<u8> {
+        let composed = match (self, c) {
+            (DeadKey::Circumflex, b'a') => 0x83,
+            (DeadKey::Circumflex, b'e') => 0x88,
+            (DeadKey::Circumflex, b'i') => 0x8C,
+            (DeadKey::Circumflex, b'o') => 0x93,
+            (DeadKey::Circumflex, b'u') => 0x96,
+            (DeadKey::Diaeresis, b'a') => 0x84,
+            (DeadKey::Diaeresis, b'e') => 0x89,
+            (DeadKey::Diaeresis, b'i') => 0x8B,
+            (DeadKey::Diaeresis, b'o') => 0x94,
+            (DeadKey::Diaeresis, b'u') => 0x81,
+            (DeadKey::Diaeresis, b'y') => 0x98,
+            (DeadKey::Diaeresis, b'A') => 0x8E,
+            (DeadKey::Diaeresis, b'O') => 0x99,
+            (DeadKey::Diaeresis, b'U') => 0x9A,
+            (DeadKey::Acute, b'a') => 0xA0,
+            (DeadKey::Acute, b'e') => 0x82,
+            (DeadKey::Acute, b'i') => 0xA1,
+            (DeadKey::Acute, b'o') => 0xA2,
+            (DeadKey::Acute, b'u') => 0xA3,
+            (DeadKey::Acute, b'E') => 0x90,
+            (DeadKey::Grave, b'a') => 0x85,
+            (DeadKey::Grave, b'e') => 0x8A,
+            (DeadKey::Grave, b'i') => 0x8D,
+            (DeadKey::Grave, b'o') => 0x95,
+            (DeadKey::Grave, b'u') => 0x97,
+            _ => return None,
+        };
+
+        Some(composed)
+    }
+}
+
+/// US QWERTY: the legend of every key, with the usual symbols on the Shift level.
+fn us(key: Key, level: Level) -> Option<Symbol> {
+    if key == NonUsBackslash {
+        return match level {
+            Base => Some(Char(b'\\')),
+            Shift => Some(Char(b'|')),
+            AltGr => None,
+        };
+    }
+
+    let c = key.character()?;
+    match level {
+        Base => Some(Char(c)),
+        Shift if key.is_keypad() => Some(Char(c)),
+        Shift => Some(Char(shifted(c))),
+        AltGr => None,
+    }
+}
+
+/// French AZERTY. Keys missing from the table behave like on `us`, with A/Q, Z/W and M moved around.
+fn french(key: Key, level: Level) -> Option<Symbol> {
+    let symbol = match (key, level) {
+        (Backtick, Base) => Char(0xFD),
+        (N1, Base) => Char(b'&'),
+        (N2, Base) => Char(0x82),
+        (N2, AltGr) => Char(b'~'),
+        (N3, Base) => Char(b'"'),
+        (N3, AltGr) => Char(b'#'),
+        (N4, Base) => Char(b'\''),
+        (N4, AltGr) => Char(b'{'),
+        (N5, Base) => Char(b'('),
+        (N5, AltGr) => Char(b'['),
+        (N6, Base) => Char(b'-'),
+        (N6, AltGr) => Char(b'|'),
+        (N7, Base) => Char(0x8A),
+        (N7, AltGr) => Char(b'`'),
+        (N8, Base) => Char(b'_'),
+        (N8, AltGr) => Char(b'\\'),
+        (N9, Base) => Char(0x87),
+        (N9, AltGr) => Char(b'^'),
+        (N0, Base) => Char(0x85),
+        (N0, AltGr) => Char(b'@'),
+        (N0 | N1 | N2 | N3 | N4 | N5 | N6 | N7 | N8 | N9, Shift) => return us(key, Base),
+        (Minus, Base) => Char(b')'),
+        (Minus, Shift) => Char(0xF8),
+        (Minus, AltGr) => Char(b']'),
+        (Equal, AltGr) => Char(b'}'),
+        (SquareBracketsOpen, Base) => Dead(DeadKey::Circumflex),
+        (SquareBracketsOpen, Shift) => Dead(DeadKey::Diaeresis),
+        (SquareBracketsClosed, Base) => Char(b'$'),
+        (SquareBracketsClosed, Shift) => Char(0x9C),
+        (SingleQuote, Base) => Char(0x97),
+        (SingleQuote, Shift) => Char(b'%'),
+        (Backslash, Base) => Char(b'*'),
+        (Backslash, Shift) => Char(0xE6),
+        (M, Base) => Char(b','),
+        (M, Shift) => Char(b'?'),
+        (Comma, Base) => Char(b';'),
+        (Comma, Shift) => Char(b'.'),
+        (Dot, Base) => Char(b':'),
+        (Dot, Shift) => Char(b'/'),
+        (Slash, Base) => Char(b'!'),
+        (Slash, Shift) => Char(0x15),
+        (NonUsBackslash, Base) => Char(b'<'),
+        (NonUsBackslash, Shift) => Char(b'>'),
+        (_, AltGr) => return None,
+        _ => {
+            let moved = match key {
+                Q => A,
+                A => Q,
+                W => Z,
+                Z => W,
+                Semicolon => M,
+                _ => key,
+            };
+            return us(moved, level);
+        }
+    };
+
+    Some(symbol)
+}
+
+/// German QWERTZ. Keys missing from the table behave like on `us`, with Y and Z swapped.
+fn german(key: Key, level: Level) -> Option<Symbol> {
+    let symbol = match (key, level) {
+        (Backtick, Base) => Dead(DeadKey::Circumflex),
+        (Backtick, Shift) => Char(0xF8),
+        (N2, Shift) => Char(b'"'),
+        (N2, AltGr) => Char(0xFD),
+        (N3, Shift) => Char(0x15),
+        (N6, Shift) => Char(b'&'),
+        (N7, Shift) => Char(b'/'),
+        (N7, AltGr) => Char(b'{'),
+        (N8, Shift) => Char(b'('),
+        (N8, AltGr) => Char(b'['),
+        (N9, Shift) => Char(b')'),
+        (N9, AltGr) => Char(b']'),
+        (N0, Shift) => Char(b'='),
+        (N0, AltGr) => Char(b'}'),
+        (Minus, Base) => Char(0xE1),
+        (Minus, Shift) => Char(b'?'),
+        (Minus, AltGr) => Char(b'\\'),
+        (Equal, Base) => Dead(DeadKey::Acute),
+        (Equal, Shift) => Dead(DeadKey::Grave),
+        (Q, AltGr) => Char(b'@'),
+        (SquareBracketsOpen, Base) => Char(0x81),
+        (SquareBracketsOpen, Shift) => Char(0x9A),
+        (SquareBracketsClosed, Base) => Char(b'+'),
+        (SquareBracketsClosed, Shift) => Char(b'*'),
+        (SquareBracketsClosed, AltGr) => Char(b'~'),
+        (Semicolon, Base) => Char(0x94),
+        (Semicolon, Shift) => Char(0x99),
+        (SingleQuote, Base) => Char(0x84),
+        (SingleQuote, Shift) => Char(0x8E),
+        (Backslash, Base) => Char(b'#'),
+        (Backslash, Shift) => Char(b'\''),
+        (M, AltGr) => Char(0xE6),
+        (Comma, Shift) => Char(b';'),
+        (Dot, Shift) => Char(b':'),
+        (Slash, Base) => Char(b'-'),
+        (Slash, Shift) => Char(b'_'),
+        (NonUsBackslash, Base) => Char(b'<'),
+        (NonUsBackslash, Shift) => Char(b'>'),
+        (NonUsBackslash, AltGr) => Char(b'|'),
+        (_, AltGr) => return None,
+        _ => {
+            let moved = match key {
+                Y => Z,
+                Z => Y,
+                _ => key,
+            };
+            return us(moved, level);
+        }
+    };
+
+    Some(symbol)
+}
+
+/// Returns the character typed by `c`'s key together with Shift on a US layout.
+fn shifted(c: u8) -> u8 {
+    match c {
+        b'a'..=b'z' => c.to_ascii_uppercase(),
+        b'1' => b'!',
+        b'2' => b'@',
+        b'3' => b'#',
+        b'4' => b'$',
+        b'5' => b'%',
+        b'6' => b'^',
+        b'7' => b'&',
+        b'8' => b'*',
+        b'9' => b'(',
+        b'0' => b')',
+        b'-' => b'_',
+        b'=' => b'+',
+        b'[' => b'{',
+        b']' => b'}',
+        b'\\' => b'|',
+        b';' => b':',
+        b'\'' => b'"',
+        b',' => b'<',
+        b'.' => b'>',
+        b'/' => b'?',
+        b'`' => b'~',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod layout_test {
+    use super::*;
+
+    #[test]
+    fn test_french_moves_letters_and_digits() {
+        let fr = KeyboardLayout::French;
+
+        assert_eq!(fr.symbol(Q, Base), Some(Char(b'a')));
+        assert_eq!(fr.symbol(A, Shift), Some(Char(b'Q')));
+        assert_eq!(fr.symbol(Semicolon, Base), Some(Char(b'm')));
+        assert_eq!(fr.symbol(N2, Base), Some(Char(0x82)));
+        assert_eq!(fr.symbol(N2, Shift), Some(Char(b'2')));
+        assert_eq!(fr.symbol(N0, AltGr), Some(Char(b'@')));
+    }
+
+    #[test]
+    fn test_german_swaps_y_and_z() {
+        let de = KeyboardLayout::German;
+
+        assert_eq!(de.symbol(Y, Base), Some(Char(b'z')));
+        assert_eq!(de.symbol(Z, Shift), Some(Char(b'Y')));
+        assert_eq!(de.symbol(Minus, Base), Some(Char(0xE1)));
+        assert_eq!(de.symbol(Q, AltGr), Some(Char(b'@')));
+        assert_eq!(de.symbol(A, AltGr), None);
+    }
+
+    #[test]
+    fn test_dead_keys_compose_with_vowels_only() {
+        assert_eq!(DeadKey::Circumflex.compose(b'e'), Some(0x88));
+        assert_eq!(DeadKey::Diaeresis.compose(b'U'), Some(0x9A));
+        assert_eq!(DeadKey::Circumflex.compose(b'x'), None);
+    }
+
+    #[test]
+    fn test_layouts_are_found_by_name_and_cycle() {
+        assert_eq!(KeyboardLayout::from_name("fr"), Some(KeyboardLayout::French));
+        assert_eq!(KeyboardLayout::from_name("xx"), None);
+        assert_eq!(KeyboardLayout::German.next(), KeyboardLayout::Us);
+    }
+}
@@ -1,8 +1,12 @@
+mod clipboard;
 mod cursor;
+pub mod keyboard;
+pub mod layout;
+pub mod mouse;
 pub mod ps2;
 mod screen;
 #[allow(clippy::module_inception)]
 mod terminal;
 pub mod vga;
 
-pub use terminal::Terminal;
+pub use terminal::{Terminal, MAX_SCREENS};
@@ -0,0 +1,296 @@
+use super::{
+    ps2::{
+        self,
+        controller::{self, ControllerError, Ps2Port, DEVICE_ACK},
+    },
+    vga::{VIEW_HEIGHT, VIEW_WIDTH},
+};
+
+/// Device command: restore the default sample rate, resolution and scaling.
+const SET_DEFAULTS: u8 = 0xF6;
+/// Device command: start sending movement packets.
+const ENABLE_DATA_REPORTING: u8 = 0xF4;
+/// Device command: set the number of samples per second, followed by the rate.
+const SET_SAMPLE_RATE: u8 = 0xF3;
+/// Device command: read the device ID.
+const GET_ID: u8 = 0xF2;
+
+/// Sample rates that switch an IntelliMouse to its 4-byte packet mode when sent in this order.
+const WHEEL_UNLOCK_SEQUENCE: [u8; 3] = [200, 100, 80];
+/// Device ID reported by a mouse with a scroll wheel once `WHEEL_UNLOCK_SEQUENCE` was sent.
+const INTELLIMOUSE_ID: u8 = 0x03;
+
+/// First packet byte: left button pressed.
+const LEFT_BUTTON_BIT: u8 = 1 << 0;
+/// First packet byte: right button pressed.
+const RIGHT_BUTTON_BIT: u8 = 1 << 1;
+/// First packet byte: middle button pressed.
+const MIDDLE_BUTTON_BIT: u8 = 1 << 2;
+/// First packet byte: always set, used to find the start of a packet.
+const ALWAYS_ONE_BIT: u8 = 1 << 3;
+/// First packet byte: the X movement is negative.
+const X_SIGN_BIT: u8 = 1 << 4;
+/// First packet byte: the Y movement is negative.
+const Y_SIGN_BIT: u8 = 1 << 5;
+/// First packet byte: the X movement did not fit in 9 bits.
+const X_OVERFLOW_BIT: u8 = 1 << 6;
+/// First packet byte: the Y movement did not fit in 9 bits.
+const Y_OVERFLOW_BIT: u8 = 1 << 7;
+
+/// Movement units the pointer travels to move by one column.
+const UNITS_PER_COLUMN: i32 = 8;
+/// Movement units the pointer travels to move by one row, cells are twice as high as they are wide.
+const UNITS_PER_ROW: i32 = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MouseButtons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+}
+
+/// A decoded mouse packet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MouseEvent {
+    /// Horizontal movement, positive to the right.
+    pub dx: i16,
+    /// Vertical movement, positive **up** as reported by the mouse.
+    pub dy: i16,
+    /// Wheel movement, negative when the wheel is turned away from the user. Always 0 without a wheel.
+    pub wheel: i8,
+    /// Buttons held down when the packet was sent.
+    pub buttons: MouseButtons,
+}
+
+/// Assembles the bytes queued by the mouse interrupt into `MouseEvent`s.
+pub struct Mouse {
+    packet: [u8; 4],
+    received: usize,
+    has_wheel: bool,
+}
+
+impl Mouse {
+    /// Creates a decoder for 3-byte packets, or 4-byte ones if `has_wheel` is set.
+    pub const fn new(has_wheel: bool) -> Self {
+        Mouse {
+            packet: [0; 4],
+            received: 0,
+            has_wheel,
+        }
+    }
+
+    /// Returns `true` if the mouse was detected as an IntelliMouse and reports wheel movement.
+    pub fn has_wheel(&self) -> bool {
+        self.has_wheel
+    }
+
+    fn packet_size(&self) -> usize {
+        if self.has_wheel {
+            4
+        } else {
+            3
+        }
+    }
+
+    /// Feeds one byte sent by the mouse.
+    ///
+    /// ### Returns:
+    /// - `Some(MouseEvent)` once a full packet was received.
+    /// - `None` while the packet is incomplete, or if it was dropped.
+    ///
+    /// ### Notes:
+    /// - A first byte without its always-one bit is dropped, which brings the decoder back in sync
+    ///   with the packet boundaries if a byte got lost.
+    /// - Packets whose movement overflowed are dropped, their deltas are meaningless.
+    pub fn decode(&mut self, byte: u8) -> Option<MouseEvent> {
+        if self.received == 0 && byte & ALWAYS_ONE_BIT == 0 {
+            return None;
+        }
+
+        self.packet[self.received] = byte;
+        self.received += 1;
+        if self.received < self.packet_size() {
+            return None;
+        }
+        self.received = 0;
+
+        let flags = self.packet[0];
+        if flags & (X_OVERFLOW_BIT | Y_OVERFLOW_BIT) != 0 {
+            return None;
+        }
+
+        Some(MouseEvent {
+            dx: movement(self.packet[1], flags & X_SIGN_BIT != 0),
+            dy: movement(self.packet[2], flags & Y_SIGN_BIT != 0),
+            wheel: if self.has_wheel { self.packet[3] as i8 } else { 0 },
+            buttons: MouseButtons {
+                left: flags & LEFT_BUTTON_BIT != 0,
+                right: flags & RIGHT_BUTTON_BIT != 0,
+                middle: flags & MIDDLE_BUTTON_BIT != 0,
+            },
+        })
+    }
+
+    /// Decodes the bytes queued by the mouse interrupt until one completes a packet.
+    pub fn read_buffered(&mut self) -> Option<MouseEvent> {
+        while let Some(byte) = ps2::read_mouse_byte() {
+            if let Some(event) = self.decode(byte) {
+                return Some(event);
+            }
+        }
+
+        None
+    }
+}
+
+/// Sign-extends a 9-bit movement whose sign bit is stored in the first packet byte.
+fn movement(low: u8, negative: bool) -> i16 {
+    if negative {
+        low as i16 - 0x100
+    } else {
+        low as i16
+    }
+}
+
+/// Sets up the mouse on the second PS/2 port and turns on its scroll wheel if it has one.
+///
+/// ### Returns:
+/// - `Ok(Mouse)` ready to decode the packets, once `ps2::enable_mouse_interrupts` is called.
+/// - `Err(ControllerError)` if the mouse does not answer or rejects a command.
+///
+/// ## SAFETY
+/// The second port must be working and interrupts disabled, as this polls the data port directly.
+pub unsafe fn init() -> Result<Mouse, ControllerError> {
+    controller::reset_device(Ps2Port::Second)?;
+    // The mouse sends its ID right after the self-test result.
+    controller::read_data()?;
+
+    send(SET_DEFAULTS)?;
+    let has_wheel = detect_wheel()?;
+    send(ENABLE_DATA_REPORTING)?;
+
+    Ok(Mouse::new(has_wheel))
+}
+
+/// Sends the IntelliMouse knock sequence and checks if the mouse changed its ID.
+fn detect_wheel() -> Result<bool, ControllerError> {
+    for rate in WHEEL_UNLOCK_SEQUENCE {
+        send(SET_SAMPLE_RATE)?;
+        send(rate)?;
+    }
+
+    send(GET_ID)?;
+    Ok(controller::read_data()? == INTELLIMOUSE_ID)
+}
+
+/// Sends a byte to the mouse and expects it to be acknowledged.
+fn send(byte: u8) -> Result<(), ControllerError> {
+    match controller::send_to_device(Ps2Port::Second, byte)? {
+        DEVICE_ACK => Ok(()),
+        res => Err(ControllerError::UnexpectedResponse(res)),
+    }
+}
+
+/// Position of the text-mode pointer, kept in movement units so slow movements still add up to a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pointer {
+    x: i32,
+    y: i32,
+}
+
+impl Pointer {
+    /// Creates a pointer in the middle of the view.
+    pub const fn new() -> Self {
+        Pointer {
+            x: (VIEW_WIDTH as i32 / 2) * UNITS_PER_COLUMN,
+            y: (VIEW_HEIGHT as i32 / 2) * UNITS_PER_ROW,
+        }
+    }
+
+    /// Moves the pointer by a mouse movement, keeping it inside the view.
+    pub fn move_by(&mut self, dx: i16, dy: i16) {
+        self.x = (self.x + dx as i32).clamp(0, VIEW_WIDTH as i32 * UNITS_PER_COLUMN - 1);
+        // The mouse counts up as positive, rows grow downwards.
+        self.y = (self.y - dy as i32).clamp(0, VIEW_HEIGHT as i32 * UNITS_PER_ROW - 1);
+    }
+
+    /// Returns the `(column, row)` of the cell under the pointer.
+    pub fn cell(&self) -> (usize, usize) {
+        ((self.x / UNITS_PER_COLUMN) as usize, (self.y / UNITS_PER_ROW) as usize)
+    }
+}
+
+#[cfg(test)]
+mod mouse_test {
+    use super::*;
+
+    fn decode_all(mouse: &mut Mouse, bytes: &[u8]) -> Option<MouseEvent> {
+        let mut event = None;
+        for &byte in bytes {
+            event = mouse.decode(byte);
+        }
+        event
+    }
+
+    #[test]
+    fn test_decode_standard_packet() {
+        let mut mouse = Mouse::new(false);
+
+        assert_eq!(mouse.decode(0x09), None);
+        assert_eq!(mouse.decode(0x05), None);
+        let event = mouse.decode(0x03).unwrap();
+        assert_eq!((event.dx, event.dy, event.wheel), (5, 3, 0));
+        assert_eq!(
+            event.buttons,
+            MouseButtons {
+                left: true,
+                right: false,
+                middle: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_negative_movement() {
+        let mut mouse = Mouse::new(false);
+
+        let event = decode_all(&mut mouse, &[0x08 | X_SIGN_BIT | Y_SIGN_BIT, 0xFF, 0xF0]).unwrap();
+        assert_eq!((event.dx, event.dy), (-1, -16));
+    }
+
+    #[test]
+    fn test_decode_wheel_packet() {
+        let mut mouse = Mouse::new(true);
+
+        assert_eq!(decode_all(&mut mouse, &[0x08, 0x00, 0x00]), None);
+        let event = mouse.decode(0xFF).unwrap();
+        assert_eq!(event.wheel, -1);
+    }
+
+    #[test]
+    fn test_decode_resyncs_and_drops_overflow() {
+        let mut mouse = Mouse::new(false);
+
+        // A lost first byte: the stray movement bytes are skipped until a valid first byte shows up.
+        assert_eq!(decode_all(&mut mouse, &[0x10, 0x20]), None);
+        assert!(decode_all(&mut mouse, &[0x0A, 0x01, 0x01]).is_some_and(|e| e.buttons.right));
+
+        assert_eq!(decode_all(&mut mouse, &[0x08 | X_OVERFLOW_BIT, 0xFF, 0x00]), None);
+        assert!(decode_all(&mut mouse, &[0x08, 0x01, 0x00]).is_some());
+    }
+
+    #[test]
+    fn test_pointer_stays_in_view() {
+        let mut pointer = Pointer::new();
+        assert_eq!(pointer.cell(), (VIEW_WIDTH / 2, VIEW_HEIGHT / 2));
+
+        pointer.move_by(i16::MAX, i16::MIN);
+        assert_eq!(pointer.cell(), (VIEW_WIDTH - 1, VIEW_HEIGHT - 1));
+
+        pointer.move_by(i16::MIN, i16::MAX);
+        assert_eq!(pointer.cell(), (0, 0));
+
+        pointer.move_by(UNITS_PER_COLUMN as i16, -(UNITS_PER_ROW as i16));
+        assert_eq!(pointer.cell(), (1, 1));
+    }
+}
@@ -0,0 +1,142 @@
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+use super::controller::{self, ControllerError, Ps2Port, DEVICE_ACK, DEVICE_RESEND};
+use crate::{idt, pit};
+
+/// Keyboard command: set the LEDs, followed by a `Leds` byte.
+const SET_LEDS: u8 = 0xED;
+/// Keyboard command: set the typematic delay and rate, followed by a typematic byte.
+const SET_TYPEMATIC: u8 = 0xF3;
+
+/// Number of times a byte is sent again when the keyboard answers `DEVICE_RESEND`.
+const MAX_RESENDS: usize = 3;
+
+/// Time the keyboard has to answer a byte once interrupts deliver its responses, in milliseconds.
+const RESPONSE_TIMEOUT_MS: u64 = 20;
+
+/// Marks `RESPONSE` as empty. Outside the range of a byte so every response can be stored.
+const NO_RESPONSE: u16 = u16::MAX;
+
+/// Last `DEVICE_ACK` or `DEVICE_RESEND` caught by the keyboard interrupt.
+static RESPONSE: AtomicU16 = AtomicU16::new(NO_RESPONSE);
+
+/// `true` once the keyboard interrupt owns the data port, so responses have to be taken from `RESPONSE`.
+static INTERRUPT_DRIVEN: AtomicBool = AtomicBool::new(false);
+
+/// State of the three keyboard LEDs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Leds {
+    pub scroll_lock: bool,
+    pub num_lock: bool,
+    pub caps_lock: bool,
+}
+
+impl Leds {
+    /// Returns the data byte of the `SET_LEDS` command.
+    fn bits(self) -> u8 {
+        (self.scroll_lock as u8) | (self.num_lock as u8) << 1 | (self.caps_lock as u8) << 2
+    }
+}
+
+/// Time a key has to be held before it starts repeating.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TypematicDelay {
+    Ms250 = 0,
+    Ms500 = 1,
+    Ms750 = 2,
+    Ms1000 = 3,
+}
+
+/// Fastest repeat rate code, about 30 characters per second.
+#[allow(dead_code)]
+pub const TYPEMATIC_RATE_FASTEST: u8 = 0x00;
+/// Slowest repeat rate code, about 2 characters per second.
+#[allow(dead_code)]
+pub const TYPEMATIC_RATE_SLOWEST: u8 = 0x1F;
+
+/// Turns the keyboard LEDs on or off.
+pub fn set_leds(leds: Leds) -> Result<(), ControllerError> {
+    send(SET_LEDS)?;
+    send(leds.bits())
+}
+
+/// Configures how fast a held key repeats.
+///
+/// ### Parameters:
+/// - `delay`: How long a key has to be held before it starts repeating.
+/// - `rate`: The 5-bit repeat rate code, from `TYPEMATIC_RATE_FASTEST` (30 cps) to `TYPEMATIC_RATE_SLOWEST` (2 cps).
+///   Higher bits are ignored.
+#[allow(dead_code)]
+pub fn set_typematic(delay: TypematicDelay, rate: u8) -> Result<(), ControllerError> {
+    send(SET_TYPEMATIC)?;
+    send((delay as u8) << 5 | (rate & 0x1F))
+}
+
+/// Sends a byte to the keyboard and waits for it to be acknowledged, sending it again when asked to.
+///
+/// ### Returns:
+/// - `Ok(())` once the keyboard answered `DEVICE_ACK`.
+/// - `Err(ControllerError::UnexpectedResponse)` if it answered anything else.
+/// - `Err(ControllerError::Timeout)` if it did not answer or kept asking for a resend.
+fn send(byte: u8) -> Result<(), ControllerError> {
+    for _ in 0..MAX_RESENDS {
+        RESPONSE.store(NO_RESPONSE, Ordering::Release);
+        controller::write_device(Ps2Port::First, byte)?;
+
+        match wait_for_response()? {
+            DEVICE_ACK => return Ok(()),
+            DEVICE_RESEND => continue,
+            res => return Err(ControllerError::UnexpectedResponse(res)),
+        }
+    }
+
+    Err(ControllerError::Timeout)
+}
+
+/// Waits for the keyboard's answer, either on the data port or through the keyboard interrupt.
+fn wait_for_response() -> Result<u8, ControllerError> {
+    if !INTERRUPT_DRIVEN.load(Ordering::Acquire) {
+        return controller::read_data();
+    }
+
+    // The timer interrupt wakes the CPU up at every tick, so the deadline is noticed even if no byte arrives.
+    let deadline = pit::deadline_ms(RESPONSE_TIMEOUT_MS);
+    loop {
+        let res = RESPONSE.swap(NO_RESPONSE, Ordering::AcqRel);
+        if res != NO_RESPONSE {
+            return Ok(res as u8);
+        }
+        if pit::ticks() >= deadline {
+            return Err(ControllerError::Timeout);
+        }
+
+        idt::disable_interrupts();
+        if RESPONSE.load(Ordering::Acquire) == NO_RESPONSE {
+            idt::enable_interrupts_and_halt();
+        } else {
+            idt::enable_interrupts();
+        }
+    }
+}
+
+/// Called by the keyboard interrupt for every byte it reads.
+///
+/// ### Returns:
+/// - `true` if `code` is a command response and was consumed.
+/// - `false` if it is a scancode.
+pub(super) fn catch_response(code: u8) -> bool {
+    match code {
+        DEVICE_ACK | DEVICE_RESEND => {
+            RESPONSE.store(code as u16, Ordering::Release);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Switches `send` to waiting for responses caught by the keyboard interrupt.
+pub(super) fn set_interrupt_driven() {
+    INTERRUPT_DRIVEN.store(true, Ordering::Release);
+}
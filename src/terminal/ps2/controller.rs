@@ -0,0 +1,315 @@
+use super::{PS2_COMMAND_PORT, PS2_DATA_PORT, PS2_INPUT_BUFFER_STATUS_BIT, PS2_OUTPUT_BUFFER_STATUS_BIT, PS2_STATUS_PORT};
+use crate::{
+    port,
+    print::{slice_to_str, u64_to_base},
+    terminal::{vga::Color, Terminal},
+};
+
+/// Number of status polls before a read or write is considered timed out.
+const TIMEOUT_POLLS: usize = 100_000;
+
+const DISABLE_FIRST_PORT: u8 = 0xAD;
+const ENABLE_FIRST_PORT: u8 = 0xAE;
+const DISABLE_SECOND_PORT: u8 = 0xA7;
+const ENABLE_SECOND_PORT: u8 = 0xA8;
+const READ_CONFIGURATION: u8 = 0x20;
+const WRITE_CONFIGURATION: u8 = 0x60;
+const SELF_TEST: u8 = 0xAA;
+const TEST_FIRST_PORT: u8 = 0xAB;
+const TEST_SECOND_PORT: u8 = 0xA9;
+const WRITE_SECOND_PORT: u8 = 0xD4;
+
+const SELF_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+
+/// Configuration byte: raise IRQ1 when the first port has data.
+const CONFIG_FIRST_PORT_IRQ: u8 = 1 << 0;
+/// Configuration byte: raise IRQ12 when the second port has data.
+const CONFIG_SECOND_PORT_IRQ: u8 = 1 << 1;
+/// Configuration byte: the clock of the second port is disabled.
+const CONFIG_SECOND_PORT_CLOCK_DISABLED: u8 = 1 << 5;
+/// Configuration byte: translate scancode set 2 from the keyboard to the set 1 our decoder expects.
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+
+/// Device command: reset and run the built-in self-test.
+const DEVICE_RESET: u8 = 0xFF;
+/// Device response: command acknowledged.
+pub const DEVICE_ACK: u8 = 0xFA;
+/// Device response: the last byte was garbled, send it again.
+pub const DEVICE_RESEND: u8 = 0xFE;
+/// Device response: the self-test after a reset passed.
+const DEVICE_SELF_TEST_PASSED: u8 = 0xAA;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerError {
+    /// The controller or a device did not answer in time.
+    Timeout,
+    /// The controller self-test returned this value instead of `0x55`.
+    SelfTestFailed(u8),
+    /// A device answered a command with this value instead of the expected one.
+    UnexpectedResponse(u8),
+}
+
+/// The two ports of the 8042 controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Port {
+    /// The keyboard port.
+    First,
+    /// The auxiliary (mouse) port.
+    Second,
+}
+
+/// State of a controller port after `init`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortStatus {
+    /// The controller is single channel, there is no such port.
+    Absent,
+    /// The interface test failed with this error code.
+    Failed(u8),
+    /// The port passed its interface test and is enabled.
+    Working,
+}
+
+/// Result of the controller bring-up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControllerReport {
+    pub first_port: PortStatus,
+    pub second_port: PortStatus,
+    /// `true` if a keyboard on the first port acknowledged the reset and passed its self-test.
+    pub keyboard: bool,
+}
+
+impl ControllerReport {
+    /// Writes one line per port and one for the keyboard, failures in `Color::Error`.
+    pub fn write_to(&self, t: &mut Terminal) {
+        for (name, status) in [("PS/2 port 1: ", self.first_port), ("PS/2 port 2: ", self.second_port)] {
+            t.write_str(name);
+            match status {
+                PortStatus::Absent => t.write_str("absent"),
+                PortStatus::Working => t.write_str("working"),
+                PortStatus::Failed(code) => {
+                    let (slice, len) = u64_to_base(code as u64, 16).unwrap();
+                    t.write_color_str("interface test failed with 0x", Color::Error as u8);
+                    t.write_color_str(slice_to_str((&slice, len)).unwrap(), Color::Error as u8);
+                }
+            }
+            t.write_str("\n");
+        }
+
+        t.write_str("keyboard: ");
+        if self.keyboard {
+            t.write_str("ready");
+        } else {
+            t.write_color_str("not responding", Color::Error as u8);
+        }
+        t.write_str("\n");
+    }
+}
+
+/// Brings up the 8042 PS/2 controller instead of trusting the firmware's configuration.
+///
+/// Both ports are disabled and the output buffer flushed, then the controller self-test and the interface
+/// tests of both ports are run. Working ports are enabled with their IRQ and scancode translation turned on,
+/// and the keyboard on the first port is reset.
+///
+/// ### Returns:
+/// - `Ok(ControllerReport)` telling which ports are alive, even if some of them failed.
+/// - `Err(ControllerError)` if the controller itself does not work.
+///
+/// ## SAFETY
+/// Must run before `enable_interrupts`, with interrupts disabled, as it polls the data port directly.
+pub unsafe fn init() -> Result<ControllerReport, ControllerError> {
+    write_command(DISABLE_FIRST_PORT)?;
+    write_command(DISABLE_SECOND_PORT)?;
+    flush_output_buffer();
+
+    let mut config = setup_configuration(read_configuration()?);
+    write_configuration(config)?;
+
+    write_command(SELF_TEST)?;
+    self_test_result(read_data()?)?;
+    // Some controllers reset themselves during the self-test.
+    write_configuration(config)?;
+
+    // A single channel controller ignores the enable command, so the clock of the second port stays disabled.
+    write_command(ENABLE_SECOND_PORT)?;
+    let dual_channel = is_dual_channel(read_configuration()?);
+    if dual_channel {
+        write_command(DISABLE_SECOND_PORT)?;
+    }
+
+    let first_port = test_port(TEST_FIRST_PORT)?;
+    let second_port = if dual_channel { test_port(TEST_SECOND_PORT)? } else { PortStatus::Absent };
+
+    if first_port == PortStatus::Working {
+        write_command(ENABLE_FIRST_PORT)?;
+        config |= CONFIG_FIRST_PORT_IRQ;
+    }
+    if second_port == PortStatus::Working {
+        write_command(ENABLE_SECOND_PORT)?;
+        config |= CONFIG_SECOND_PORT_IRQ;
+    }
+    write_configuration(config)?;
+
+    let keyboard = first_port == PortStatus::Working && reset_device(Ps2Port::First).is_ok();
+
+    Ok(ControllerReport {
+        first_port,
+        second_port,
+        keyboard,
+    })
+}
+
+/// Sends a byte to the device on `port` and waits for its answer, resending it if the device asks to.
+///
+/// ### Returns:
+/// - `Ok(u8)` with the first byte that is not `DEVICE_RESEND`.
+/// - `Err(ControllerError::Timeout)` if the device does not answer, or keeps asking for a resend.
+pub fn send_to_device(port: Ps2Port, byte: u8) -> Result<u8, ControllerError> {
+    const MAX_RESENDS: usize = 3;
+
+    for _ in 0..MAX_RESENDS {
+        write_device(port, byte)?;
+        match read_data()? {
+            DEVICE_RESEND => continue,
+            res => return Ok(res),
+        }
+    }
+
+    Err(ControllerError::Timeout)
+}
+
+/// Resets the device on `port` and checks that it passes its self-test.
+pub fn reset_device(port: Ps2Port) -> Result<(), ControllerError> {
+    match send_to_device(port, DEVICE_RESET)? {
+        DEVICE_ACK => {}
+        res => return Err(ControllerError::UnexpectedResponse(res)),
+    }
+
+    match read_data()? {
+        DEVICE_SELF_TEST_PASSED => Ok(()),
+        res => Err(ControllerError::UnexpectedResponse(res)),
+    }
+}
+
+/// Writes a byte to the device on `port` without waiting for an answer.
+pub fn write_device(port: Ps2Port, byte: u8) -> Result<(), ControllerError> {
+    if port == Ps2Port::Second {
+        write_command(WRITE_SECOND_PORT)?;
+    }
+    write_data(byte)
+}
+
+/// Runs an interface test command and converts its result.
+fn test_port(command: u8) -> Result<PortStatus, ControllerError> {
+    write_command(command)?;
+
+    Ok(port_status(read_data()?))
+}
+
+/// Returns the configuration byte used while testing the controller: both port IRQs off, so no test
+/// answer is taken for device data, and scancode translation on.
+fn setup_configuration(config: u8) -> u8 {
+    config & !(CONFIG_FIRST_PORT_IRQ | CONFIG_SECOND_PORT_IRQ) | CONFIG_TRANSLATION
+}
+
+/// Converts the answer to `SELF_TEST`.
+fn self_test_result(res: u8) -> Result<(), ControllerError> {
+    match res {
+        SELF_TEST_PASSED => Ok(()),
+        res => Err(ControllerError::SelfTestFailed(res)),
+    }
+}
+
+/// Returns `true` if the second port clock runs in `config`, read after `ENABLE_SECOND_PORT`.
+fn is_dual_channel(config: u8) -> bool {
+    config & CONFIG_SECOND_PORT_CLOCK_DISABLED == 0
+}
+
+/// Converts the answer to an interface test command.
+fn port_status(code: u8) -> PortStatus {
+    match code {
+        PORT_TEST_PASSED => PortStatus::Working,
+        code => PortStatus::Failed(code),
+    }
+}
+
+fn read_configuration() -> Result<u8, ControllerError> {
+    write_command(READ_CONFIGURATION)?;
+    read_data()
+}
+
+fn write_configuration(config: u8) -> Result<(), ControllerError> {
+    write_command(WRITE_CONFIGURATION)?;
+    write_data(config)
+}
+
+/// Discards whatever is left in the output buffer.
+fn flush_output_buffer() {
+    while status() & PS2_OUTPUT_BUFFER_STATUS_BIT != 0 {
+        unsafe { port::read(PS2_DATA_PORT) };
+    }
+}
+
+/// Writes a command to the controller once its input buffer is empty.
+pub fn write_command(command: u8) -> Result<(), ControllerError> {
+    wait_for_status(PS2_INPUT_BUFFER_STATUS_BIT, false)?;
+    unsafe { port::write(PS2_COMMAND_PORT, command) };
+    Ok(())
+}
+
+/// Writes to the data port once the controller's input buffer is empty.
+pub fn write_data(byte: u8) -> Result<(), ControllerError> {
+    wait_for_status(PS2_INPUT_BUFFER_STATUS_BIT, false)?;
+    unsafe { port::write(PS2_DATA_PORT, byte) };
+    Ok(())
+}
+
+/// Reads from the data port once the controller's output buffer is full.
+pub fn read_data() -> Result<u8, ControllerError> {
+    wait_for_status(PS2_OUTPUT_BUFFER_STATUS_BIT, true)?;
+    Ok(unsafe { port::read(PS2_DATA_PORT) })
+}
+
+/// Reads the controller's status register.
+pub fn status() -> u8 {
+    unsafe { port::read(PS2_STATUS_PORT) }
+}
+
+/// Polls the status register until `bit` is `set`, giving up after `TIMEOUT_POLLS` attempts.
+fn wait_for_status(bit: u8, set: bool) -> Result<(), ControllerError> {
+    for _ in 0..TIMEOUT_POLLS {
+        if (status() & bit != 0) == set {
+            return Ok(());
+        }
+        port::wait();
+    }
+
+    Err(ControllerError::Timeout)
+}
+
+#[cfg(test)]
+mod controller_test {
+    use super::*;
+
+    #[test]
+    fn test_setup_configuration() {
+        // IRQs on and translation off as a firmware might leave them, other bits are kept.
+        assert_eq!(setup_configuration(0b0010_0111), 0b0110_0100);
+        assert_eq!(setup_configuration(CONFIG_TRANSLATION), CONFIG_TRANSLATION);
+    }
+
+    #[test]
+    fn test_self_test_result() {
+        assert_eq!(self_test_result(0x55), Ok(()));
+        assert_eq!(self_test_result(0xFC), Err(ControllerError::SelfTestFailed(0xFC)));
+    }
+
+    #[test]
+    fn test_port_decoding() {
+        assert!(is_dual_channel(0b0000_0011));
+        assert!(!is_dual_channel(CONFIG_SECOND_PORT_CLOCK_DISABLED));
+        assert_eq!(port_status(0x00), PortStatus::Working);
+        assert_eq!(port_status(0x03), PortStatus::Failed(0x03));
+    }
+}
@@ -0,0 +1,555 @@
+use crate::{
+    idt::{self, irq, InterruptFrame},
+    pic::Irq,
+    port,
+    ring_buffer::RingBuffer,
+};
+
+pub mod command;
+pub mod controller;
+
+pub const PS2_DATA_PORT: u16 = 0x60;
+pub const PS2_STATUS_PORT: u16 = 0x64;
+/// Same port as `PS2_STATUS_PORT`: reads return the status, writes send a command to the controller.
+pub const PS2_COMMAND_PORT: u16 = 0x64;
+pub const PS2_OUTPUT_BUFFER_STATUS_BIT: u8 = 1;
+/// Set while the controller has not yet consumed the last byte written to it.
+pub const PS2_INPUT_BUFFER_STATUS_BIT: u8 = 1 << 1;
+/// Set when the byte in the output buffer comes from the second port, the mouse.
+pub const PS2_AUX_DATA_STATUS_BIT: u8 = 1 << 5;
+
+/// Number of raw scancodes that can be queued by the keyboard interrupt before new ones get dropped.
+const SCANCODE_BUFFER_SIZE: usize = 128;
+
+/// Number of raw mouse bytes that can be queued by the mouse interrupt, a bit more than 60 packets.
+const MOUSE_BUFFER_SIZE: usize = 256;
+
+/// Scancodes read by `keyboard_interrupt`, waiting to be consumed by the main loop.
+static SCANCODES: RingBuffer<SCANCODE_BUFFER_SIZE> = RingBuffer::new();
+
+/// Packet bytes read by `mouse_interrupt`, waiting to be consumed by the main loop.
+static MOUSE_BYTES: RingBuffer<MOUSE_BUFFER_SIZE> = RingBuffer::new();
+
+/// Installs the IRQ1 handler and unmasks the keyboard line. From then on the scancodes are queued
+/// in the background and must be read with `read_scancode` or `wait_for_input`, `read_if_ready`
+/// would race the interrupt handler for the data port.
+///
+/// ## SAFETY
+/// The IDT and PIC must be initialized, and the PIT running to time out keyboard commands.
+/// Must not be called from an interrupt handler.
+pub unsafe fn enable_interrupts() {
+    command::set_interrupt_driven();
+    irq::set_handler(Irq::Keyboard, keyboard_interrupt);
+}
+
+/// IRQ1 handler: see `queue_data_byte`.
+fn keyboard_interrupt(_frame: &mut InterruptFrame) {
+    queue_data_byte();
+}
+
+/// Installs the IRQ12 handler and unmasks the mouse line. The mouse must already be set up with
+/// `mouse::init`, its packets are then queued in the background and read with `read_mouse_byte`.
+///
+/// ## SAFETY
+/// The IDT and PIC must be initialized. Must not be called from an interrupt handler.
+pub unsafe fn enable_mouse_interrupts() {
+    irq::set_handler(Irq::Mouse, mouse_interrupt);
+}
+
+/// IRQ12 handler: see `queue_data_byte`.
+fn mouse_interrupt(_frame: &mut InterruptFrame) {
+    queue_data_byte();
+}
+
+/// Moves the byte in the controller output buffer to the queue of the device that sent it.
+///
+/// Both IRQ handlers go through here: the controller has a single output buffer, so IRQ1 can find a
+/// mouse byte in it and IRQ12 a scancode. Keyboard bytes that answer a command are not queued.
+fn queue_data_byte() {
+    let status = controller::status();
+    if status & PS2_OUTPUT_BUFFER_STATUS_BIT == 0 {
+        return;
+    }
+
+    let byte = unsafe { port::read(PS2_DATA_PORT) };
+
+    if status & PS2_AUX_DATA_STATUS_BIT != 0 {
+        MOUSE_BYTES.push(byte);
+    } else if !command::catch_response(byte) {
+        SCANCODES.push(byte);
+    }
+}
+
+/// Pops the oldest raw scancode queued by the keyboard interrupt, if any.
+pub fn read_scancode() -> Option<u8> {
+    SCANCODES.pop()
+}
+
+/// Pops the oldest packet byte queued by the mouse interrupt, if any.
+pub fn read_mouse_byte() -> Option<u8> {
+    MOUSE_BYTES.pop()
+}
+
+/// Blocks until the keyboard or the mouse interrupt queued a byte, halting the CPU in between interrupts.
+///
+/// Nothing is consumed, the bytes are left for `read_scancode` and `read_mouse_byte`.
+pub fn wait_for_input() {
+    idt::disable_interrupts();
+    while SCANCODES.is_empty() && MOUSE_BYTES.is_empty() {
+        idt::enable_interrupts_and_halt();
+        idt::disable_interrupts();
+    }
+    idt::enable_interrupts();
+}
+
+/// Reads from the PS2 data port if the PS2 status port is ready. Returns `Some(KeyScanCode)`
+/// if the converted scancode is a supported character.
+///
+/// This polls the controller directly and is meant for early boot, before `enable_interrupts`.
+///
+/// /// ### Example Usage:
+/// ```
+/// let mut v = Vga::new();
+///
+/// if let Some(c) = read_if_ready() == KeyScanCode::A {
+///     v.write_char(b'a');
+/// }
+#[allow(dead_code)]
+pub fn read_if_ready() -> Option<Key> {
+    if !is_ps2_data_available() {
+        return None;
+    }
+
+    let code = unsafe { port::read(PS2_DATA_PORT) };
+
+    SCANCODE_TO_KEY[code as usize]
+}
+
+/// Returns `true` if the PS2 input buffer has data ready to be read,
+/// meaning the least significant bit of the PS2 status port is set.
+fn is_ps2_data_available() -> bool {
+    controller::status() & PS2_OUTPUT_BUFFER_STATUS_BIT != 0
+}
+
+/// A physical key. Printable keys carry the ASCII value they produce on a US layout without modifiers.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Key {
+    Tab,
+    Enter,
+    ArrowUp,
+    Backspace,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    LeftAlt,
+    CapsLock,
+    RightCtrl,
+    RightAlt,
+    NumLock,
+    ScrollLock,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Pause,
+    PrintScreen,
+    /// The extra key next to left Shift on ISO keyboards (`<>` on French and German ones).
+    NonUsBackslash,
+    A = b'a',
+    B = b'b',
+    C = b'c',
+    D = b'd',
+    E = b'e',
+    F = b'f',
+    G = b'g',
+    H = b'h',
+    I = b'i',
+    J = b'j',
+    K = b'k',
+    L = b'l',
+    M = b'm',
+    N = b'n',
+    O = b'o',
+    P = b'p',
+    Q = b'q',
+    R = b'r',
+    S = b's',
+    T = b't',
+    U = b'u',
+    V = b'v',
+    W = b'w',
+    X = b'x',
+    Y = b'y',
+    Z = b'z',
+    N0 = b'0',
+    N1 = b'1',
+    N2 = b'2',
+    N3 = b'3',
+    N4 = b'4',
+    N5 = b'5',
+    N6 = b'6',
+    N7 = b'7',
+    N8 = b'8',
+    N9 = b'9',
+    Dot = b'.',
+    Star = b'*',
+    Space = b' ',
+    Minus = b'-',
+    Equal = b'=',
+    Slash = b'/',
+    Comma = b',',
+    Backtick = b'`',
+    Semicolon = b';',
+    Backslash = b'\\',
+    SingleQuote = b'\'',
+    SquareBracketsOpen = b'[',
+    SquareBracketsClosed = b']',
+    // Keypad keys live above the ASCII range so they never collide with the characters they produce.
+    Keypad0 = 0x80,
+    Keypad1,
+    Keypad2,
+    Keypad3,
+    Keypad4,
+    Keypad5,
+    Keypad6,
+    Keypad7,
+    Keypad8,
+    Keypad9,
+    KeypadDot,
+    KeypadPlus,
+    KeypadMinus,
+    KeypadSlash,
+    KeypadEnter,
+}
+
+impl Key {
+    /// Returns the unmodified ASCII character of a printable key, `None` for control and modifier keys.
+    pub fn character(self) -> Option<u8> {
+        match self {
+            Keypad0 | Keypad1 | Keypad2 | Keypad3 | Keypad4 | Keypad5 | Keypad6 | Keypad7 | Keypad8 | Keypad9 => Some(b'0' + (self as u8 - Keypad0 as u8)),
+            KeypadDot => Some(b'.'),
+            KeypadPlus => Some(b'+'),
+            KeypadMinus => Some(b'-'),
+            KeypadSlash => Some(b'/'),
+            _ => match self as u8 {
+                c @ b' '..=b'~' => Some(c),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns `true` for the keys of the numeric keypad, including the `Star` key.
+    pub fn is_keypad(self) -> bool {
+        self == Star || self as u8 >= Keypad0 as u8
+    }
+
+    /// Returns the navigation key a keypad key acts as while Num Lock is off.
+    pub fn keypad_navigation(self) -> Option<Key> {
+        match self {
+            Keypad0 => Some(Insert),
+            Keypad1 => Some(End),
+            Keypad2 => Some(ArrowDown),
+            Keypad3 => Some(PageDown),
+            Keypad4 => Some(ArrowLeft),
+            Keypad6 => Some(ArrowRight),
+            Keypad7 => Some(Home),
+            Keypad8 => Some(ArrowUp),
+            Keypad9 => Some(PageUp),
+            KeypadDot => Some(Delete),
+            _ => None,
+        }
+    }
+}
+
+/// Converts the second byte of a `0xE0`-prefixed scancode, with the break bit cleared, to its key.
+///
+/// `0x2A` and `0x36` are deliberately missing: those "fake shifts" are sent around extended keys
+/// (and as the first half of the Print Screen sequence) and carry no information.
+pub fn extended_scancode_to_key(code: u8) -> Option<Key> {
+    match code {
+        0x1C => Some(KeypadEnter),
+        0x1D => Some(RightCtrl),
+        0x35 => Some(KeypadSlash),
+        0x37 => Some(PrintScreen),
+        0x38 => Some(RightAlt),
+        0x46 => Some(Pause),
+        0x47 => Some(Home),
+        0x48 => Some(ArrowUp),
+        0x49 => Some(PageUp),
+        0x4B => Some(ArrowLeft),
+        0x4D => Some(ArrowRight),
+        0x4F => Some(End),
+        0x50 => Some(ArrowDown),
+        0x51 => Some(PageDown),
+        0x52 => Some(Insert),
+        0x53 => Some(Delete),
+        _ => None,
+    }
+}
+
+use Key::*;
+/// Conversion table for all characters currently supported by our kernel for PS2 input.
+pub const SCANCODE_TO_KEY: [Option<Key>; 256] = [
+    None,
+    None,
+    Some(N1),
+    Some(N2),
+    Some(N3),
+    Some(N4),
+    Some(N5),
+    Some(N6),
+    Some(N7),
+    Some(N8),
+    Some(N9),
+    Some(N0),
+    Some(Minus),
+    Some(Equal),
+    Some(Backspace),
+    Some(Tab),
+    Some(Q),
+    Some(W),
+    Some(E),
+    Some(R),
+    Some(T),
+    Some(Y),
+    Some(U),
+    Some(I),
+    Some(O),
+    Some(P),
+    Some(SquareBracketsOpen),
+    Some(SquareBracketsClosed),
+    Some(Enter),
+    Some(LeftCtrl),
+    Some(A),
+    Some(S),
+    Some(D),
+    Some(F),
+    Some(G),
+    Some(H),
+    Some(J),
+    Some(K),
+    Some(L),
+    Some(Semicolon),
+    Some(SingleQuote),
+    Some(Backtick),
+    Some(LeftShift),
+    Some(Backslash),
+    Some(Z),
+    Some(X),
+    Some(C),
+    Some(V),
+    Some(B),
+    Some(N),
+    Some(M),
+    Some(Comma),
+    Some(Dot),
+    Some(Slash),
+    Some(RightShift),
+    Some(Star),
+    Some(LeftAlt),
+    Some(Space),
+    Some(CapsLock),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(NumLock),
+    Some(ScrollLock),
+    Some(Keypad7),
+    Some(Keypad8),
+    Some(Keypad9),
+    Some(KeypadMinus),
+    Some(Keypad4),
+    Some(Keypad5),
+    Some(Keypad6),
+    Some(KeypadPlus),
+    Some(Keypad1),
+    Some(Keypad2),
+    Some(Keypad3),
+    Some(Keypad0),
+    Some(KeypadDot),
+    None,
+    None,
+    Some(NonUsBackslash),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+];
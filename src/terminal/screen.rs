@@ -1,16 +1,39 @@
+use core::ops::Range;
+
 use super::{
+    keyboard::KeyEvent,
     ps2::Key,
     vga::{flush_vga, Color, Entry},
 };
 
 pub const BUFFER_SIZE: usize = 1000;
 
+/// A range of selected entries, between two positions in the buffer like the cursor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    /// Where the selection started, it stays put while the selection grows or shrinks.
+    pub anchor: usize,
+    /// The moving end of the selection.
+    pub head: usize,
+}
+
+impl Selection {
+    /// Returns the selected indices of the buffer, whichever side of the anchor the head is on.
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Screen {
     pub buffer: [u16; BUFFER_SIZE],
     pub cursor: usize,
     pub last_entry_index: usize,
     pub rows_scrolled: usize,
+    /// Highlighted entries, cleared whenever the buffer changes.
+    pub selection: Option<Selection>,
+    /// VGA attribute used by `write`.
+    pub color: u8,
 }
 
 impl Screen {
@@ -20,14 +43,19 @@ impl Screen {
             cursor: 0,
             last_entry_index: 0,
             rows_scrolled: 0,
+            selection: None,
+            color: Color::Default as u8,
         }
     }
 
-    pub fn handle_key(&mut self, key: Key) {
+    pub fn handle_key(&mut self, event: KeyEvent) {
         use Key::*;
-        match key {
+        if !event.pressed {
+            return;
+        }
+        match event.key {
             Tab => {}
-            Enter => self.write(b'\n'),
+            Enter | KeypadEnter => self.write(b'\n'),
             Backspace => {
                 if self.cursor > 0 {
                     self.cursor -= 1;
@@ -36,17 +64,30 @@ impl Screen {
             }
             ArrowUp => self.scroll(1),
             ArrowDown => self.scroll(-1),
-            ArrowLeft => {
-                if self.cursor > 0 {
+            ArrowLeft | ArrowRight => {
+                let anchor = self.selection.map_or(self.cursor, |s| s.anchor);
+
+                if event.key == ArrowLeft && self.cursor > 0 {
                     self.cursor -= 1;
+                } else if event.key == ArrowRight && self.cursor < BUFFER_SIZE - 1 && self.cursor <= self.last_entry_index {
+                    self.cursor += 1;
+                }
+
+                // Shift + arrows select from where the cursor was, plain arrows drop the selection.
+                if event.modifiers.shift {
+                    self.selection = Some(Selection { anchor, head: self.cursor });
+                } else {
+                    self.selection = None;
                 }
             }
-            ArrowRight => {
-                if self.cursor < BUFFER_SIZE - 1 && self.cursor <= self.last_entry_index {
-                    self.cursor += 1;
+            _ => {
+                if let Some(accent) = event.accent {
+                    self.write(accent);
+                }
+                if let Some(c) = event.character {
+                    self.write(c);
                 }
             }
-            _ => self.write(key as u8),
         }
     }
 
@@ -60,20 +101,47 @@ impl Screen {
         }
     }
 
+    /// Starts a new, empty selection at `index`, clamped to the written part of the buffer.
+    pub fn start_selection(&mut self, index: usize) {
+        let index = index.min(self.last_entry_index);
+        self.selection = Some(Selection { anchor: index, head: index });
+    }
+
+    /// Moves the head of the selection so it includes the entry at `index`, starting one there if needed.
+    pub fn extend_selection(&mut self, index: usize) {
+        let index = index.min(self.last_entry_index);
+        let anchor = self.selection.map_or(index, |s| s.anchor);
+
+        let head = if index >= anchor { (index + 1).min(self.last_entry_index) } else { index };
+        self.selection = Some(Selection { anchor, head });
+    }
+
+    /// Returns the selected entries, `None` if nothing is selected.
+    pub fn selected(&self) -> Option<&[u16]> {
+        let range = self.selection?.range();
+        if range.is_empty() {
+            return None;
+        }
+
+        Some(&self.buffer[range])
+    }
+
+    /// Returns `true` if the entry at `index` is part of the selection.
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.selection.is_some_and(|s| s.range().contains(&index))
+    }
+
     pub fn write(&mut self, character: u8) {
-        self.write_color(character, Color::Default as u8);
+        self.write_color(character, self.color);
     }
 
     pub fn write_color(&mut self, character: u8, color: u8) {
         if self.cursor >= BUFFER_SIZE - 1 {
             return;
         }
-        let mut index = BUFFER_SIZE - 2;
-        while index + 1 > self.cursor {
-            self.buffer[index + 1] = self.buffer[index];
-            index -= 1;
-        }
+        self.buffer.copy_within(self.cursor..BUFFER_SIZE - 1, self.cursor + 1);
 
+        self.selection = None;
         self.last_entry_index += 1;
         self.buffer[self.cursor] = Entry::new_with_color(character, color).to_u16();
 
@@ -102,7 +170,95 @@ impl Screen {
             self.buffer[index] = self.buffer[index + 1];
             index += 1;
         }
+        self.selection = None;
         self.last_entry_index -= 1;
         self.buffer[index] = Entry::new(b' ').to_u16();
     }
 }
+
+#[cfg(test)]
+mod screen_test {
+    use super::*;
+    use crate::terminal::keyboard::Modifiers;
+
+    fn text(screen: &Screen) -> [u8; 3] {
+        [0, 1, 2].map(|index| screen.buffer[index] as u8)
+    }
+
+    #[test]
+    fn test_insert_at_start_of_buffer() {
+        let mut screen = Screen::default();
+
+        // The shifting loop used to step its index below 0 when the cursor was on the first entry.
+        screen.write(b'b');
+        screen.cursor = 0;
+        screen.write(b'a');
+        screen.cursor = 2;
+        screen.write(b'c');
+
+        assert_eq!(&text(&screen), b"abc");
+        assert_eq!((screen.cursor, screen.last_entry_index), (3, 3));
+    }
+
+    fn press(key: Key, shift: bool) -> KeyEvent {
+        KeyEvent {
+            key,
+            modifiers: Modifiers { shift, ..Modifiers::default() },
+            pressed: true,
+            character: None,
+            accent: None,
+        }
+    }
+
+    fn selected_text(screen: &Screen) -> Option<[u8; 3]> {
+        let mut text = [0; 3];
+        for (c, &entry) in text.iter_mut().zip(screen.selected()?) {
+            *c = (entry & 0xFF) as u8;
+        }
+        Some(text)
+    }
+
+    #[test]
+    fn test_shift_arrows_select_from_cursor() {
+        let mut screen = Screen::default();
+        screen.write_str("hello");
+
+        screen.handle_key(press(Key::ArrowLeft, true));
+        screen.handle_key(press(Key::ArrowLeft, true));
+        screen.handle_key(press(Key::ArrowLeft, true));
+        assert_eq!(screen.selection, Some(Selection { anchor: 5, head: 2 }));
+        assert_eq!(selected_text(&screen), Some(*b"llo"));
+
+        screen.handle_key(press(Key::ArrowRight, true));
+        assert_eq!(screen.selection.unwrap().range(), 3..5);
+
+        screen.handle_key(press(Key::ArrowRight, false));
+        assert_eq!(screen.selection, None);
+    }
+
+    #[test]
+    fn test_mouse_selection_includes_both_ends() {
+        let mut screen = Screen::default();
+        screen.write_str("abcdef");
+
+        screen.start_selection(1);
+        assert_eq!(screen.selected(), None);
+        screen.extend_selection(3);
+        assert_eq!(selected_text(&screen), Some(*b"bcd"));
+        assert!(screen.is_selected(3) && !screen.is_selected(4));
+
+        screen.extend_selection(100);
+        assert_eq!(screen.selection.unwrap().range(), 1..6);
+    }
+
+    #[test]
+    fn test_editing_clears_selection() {
+        let mut screen = Screen::default();
+        screen.write_str("abc");
+        screen.start_selection(0);
+        screen.extend_selection(2);
+
+        screen.write(b'd');
+        assert_eq!(screen.selection, None);
+    }
+}
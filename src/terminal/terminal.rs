@@ -1,10 +1,26 @@
-use super::{ps2::Key, screen::Screen};
+use core::fmt;
 
-const NBR_OF_SCREENS_PER_TERMINAL: usize = 5;
+use super::{
+    clipboard::Clipboard,
+    keyboard::KeyEvent,
+    mouse::{MouseButtons, MouseEvent, Pointer},
+    ps2::Key,
+    screen::Screen,
+    vga,
+};
+
+/// Number of screens of a terminal, `Tab` cycles through the first `screen_count` of them.
+pub const MAX_SCREENS: usize = 5;
 
 pub struct Terminal {
     active_screen: usize,
-    screens: [Screen; NBR_OF_SCREENS_PER_TERMINAL],
+    screens: [Screen; MAX_SCREENS],
+    screen_count: usize,
+    /// The mouse pointer, only drawn once the mouse sent its first packet.
+    pointer: Option<Pointer>,
+    /// Buttons held down in the previous mouse packet, to tell presses from drags.
+    buttons: MouseButtons,
+    clipboard: Clipboard,
 }
 
 impl Terminal {
@@ -15,26 +31,95 @@ impl Terminal {
     pub fn default() -> Terminal {
         Terminal {
             active_screen: 0,
-            screens: [Screen::default(); NBR_OF_SCREENS_PER_TERMINAL],
+            screens: [Screen::default(); MAX_SCREENS],
+            screen_count: MAX_SCREENS,
+            pointer: None,
+            buttons: MouseButtons::default(),
+            clipboard: Clipboard::new(),
+        }
+    }
+
+    /// Limits the screens `Tab` cycles through to the first `count`, clamped to `1..=MAX_SCREENS`.
+    pub fn set_screen_count(&mut self, count: usize) {
+        self.screen_count = count.clamp(1, MAX_SCREENS);
+        if self.active_screen >= self.screen_count {
+            self.active_screen = 0;
         }
     }
 
-    /// Handles a key press event by updating the terminal's state.
+    /// Sets the VGA attribute of the text written from now on, on every screen.
+    pub fn set_color(&mut self, color: u8) {
+        for screen in self.screens.iter_mut() {
+            screen.color = color;
+        }
+    }
+
+    /// Handles a key event by updating the terminal's state.
     ///
-    /// If the key is the `Tab` key, it switches to the next screen. Otherwise, the key event is passed
+    /// If the key is a press of the `Tab` key, it switches to the next screen. Ctrl + C copies the selection of the
+    /// active screen to the clipboard and Ctrl + V pastes it at the cursor. Otherwise, the key event is passed
     /// to the active screen for processing.
     ///
     /// # Parameters
-    /// - `key`: The key that was pressed.
-    pub fn handle_key(&mut self, key: Key) {
-        match key {
-            Key::Tab => {
+    /// - `event`: The decoded key press or release.
+    pub fn handle_key(&mut self, event: KeyEvent) {
+        match event.key {
+            Key::Tab if event.pressed => {
                 self.active_screen += 1;
-                if self.active_screen >= NBR_OF_SCREENS_PER_TERMINAL {
+                if self.active_screen >= self.screen_count {
                     self.active_screen = 0;
                 }
             }
-            _ => self.screens[self.active_screen].handle_key(key),
+            Key::C if event.pressed && event.modifiers.ctrl => self.copy(),
+            Key::V if event.pressed && event.modifiers.ctrl => self.paste(),
+            _ => self.screens[self.active_screen].handle_key(event),
+        }
+    }
+
+    /// Handles a mouse event: moves the pointer, and scrolls the active screen when the wheel turns.
+    ///
+    /// Pressing the left button starts a selection under the pointer and dragging extends it,
+    /// a middle click pastes the clipboard at the cursor.
+    ///
+    /// # Parameters
+    /// - `event`: The decoded mouse packet.
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        let pointer = self.pointer.get_or_insert(Pointer::new());
+        pointer.move_by(event.dx, event.dy);
+        let (column, row) = pointer.cell();
+
+        let screen = &mut self.screens[self.active_screen];
+        // Turning the wheel away from the user scrolls up, like `ArrowUp`.
+        if event.wheel != 0 {
+            screen.scroll(-(event.wheel as isize));
+        }
+
+        if event.buttons.left {
+            let index = vga::entry_index_at(screen, column, row);
+            if self.buttons.left {
+                screen.extend_selection(index);
+            } else {
+                screen.start_selection(index);
+            }
+        }
+        if event.buttons.middle && !self.buttons.middle {
+            self.paste();
+        }
+
+        self.buttons = event.buttons;
+    }
+
+    /// Copies the selection of the active screen to the clipboard, the previous content is kept if nothing is selected.
+    fn copy(&mut self) {
+        if let Some(entries) = self.screens[self.active_screen].selected() {
+            self.clipboard.copy(entries);
+        }
+    }
+
+    /// Writes the content of the clipboard at the cursor of the active screen.
+    fn paste(&mut self) {
+        for &c in self.clipboard.contents() {
+            self.screens[self.active_screen].write(c);
         }
     }
 
@@ -49,5 +134,18 @@ impl Terminal {
 
     pub fn flush(&self) {
         self.screens[self.active_screen].flush();
+
+        if let Some(pointer) = self.pointer {
+            let (column, row) = pointer.cell();
+            vga::invert_cell(column, row).unwrap();
+        }
+    }
+}
+
+/// Lets `write!` format into the active screen, e.g. from the panic handler.
+impl fmt::Write for Terminal {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        Terminal::write_str(self, string);
+        Ok(())
     }
 }
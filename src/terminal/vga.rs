@@ -1,5 +1,7 @@
 use core::ptr::{read_volatile, write_volatile};
 
+use crate::memory::paging;
+
 use super::{
     cursor::Cursor,
     screen::{Screen, BUFFER_SIZE},
@@ -14,8 +16,8 @@ pub const VIEW_HEIGHT: usize = 25;
 /// The total number of character positions in the viewable area (width x height).
 pub const VIEW_BUFFER_SIZE: usize = VIEW_WIDTH * VIEW_HEIGHT;
 
-/// The base memory address of the VGA buffer for text mode display.
-const VGA_BUFFER_ADDR: *mut u16 = 0xB8000 as *mut u16;
+/// The virtual address of the VGA buffer for text mode display, mapped by `paging::init`.
+const VGA_BUFFER_ADDR: *mut u16 = paging::VGA_BUFFER as *mut u16;
 
 /// Flushes the contents of the screen buffer to the VGA screen, rendering characters, handling newlines,
 /// and updating the cursor position. It checks for viewport boundaries and ensures the screen's contents
@@ -52,6 +54,7 @@ pub fn flush_vga(t: &Screen) {
             };
         }
 
+        let selected = t.is_selected(view_start_index + relative_index);
         match (entry & 0xFF) as u8 {
             b'\n' => {
                 let padding = VIEW_WIDTH - (padded_relative_index % VIEW_WIDTH) - 1;
@@ -60,22 +63,85 @@ pub fn flush_vga(t: &Screen) {
                 for i in 0..(padding + 1) {
                     write_entry_to_vga(padded_relative_index + i, Entry::new(b' ').to_u16()).unwrap();
                 }
+                // A selected newline shows as a highlighted blank at the end of its line.
+                if selected {
+                    write_entry_to_vga(padded_relative_index, invert_colors(Entry::new(b' ').to_u16())).unwrap();
+                }
             }
+            _ if selected => write_entry_to_vga(padded_relative_index, invert_colors(entry)).unwrap(),
             _ => write_entry_to_vga(padded_relative_index, entry).unwrap(),
         }
     }
 }
 
+/// Finds the entry of the screen buffer that `flush_vga` renders at `column`, `row` of the view.
+///
+/// ### Returns:
+/// The index of the entry in `t.buffer`. The blank cells after a newline belong to the newline, and cells
+/// past the written part of the buffer map to `t.last_entry_index`.
+pub fn entry_index_at(t: &Screen, column: usize, row: usize) -> usize {
+    let target = column.min(VIEW_WIDTH - 1) + VIEW_WIDTH * row;
+    let mut view_padding_whitespace: usize = 0;
+
+    let view_start_index = calculate_view_start_index(t);
+    for (relative_index, &entry) in t.buffer.iter().skip(view_start_index).enumerate() {
+        let index = view_start_index + relative_index;
+        let padded_relative_index = relative_index + view_padding_whitespace;
+        if index >= t.last_entry_index || padded_relative_index > target {
+            break;
+        }
+
+        match (entry & 0xFF) as u8 {
+            b'\n' => {
+                let padding = VIEW_WIDTH - (padded_relative_index % VIEW_WIDTH) - 1;
+                if target <= padded_relative_index + padding {
+                    return index;
+                }
+                view_padding_whitespace += padding;
+            }
+            _ if padded_relative_index == target => return index,
+            _ => {}
+        }
+    }
+
+    t.last_entry_index
+}
+
+/// Inverts the colors of the cell at `column`, `row` of the VGA view, used to draw the mouse pointer
+/// on top of what `flush_vga` rendered.
+///
+/// ### Notes:
+/// - The next `flush_vga` rewrites the cell with its original colors, as it differs from the inverted one.
+/// - The bright bit of the foreground is dropped so it does not turn into the blink bit of the background.
+pub fn invert_cell(column: usize, row: usize) -> Result<(), OutOfBoundsErr> {
+    if column >= VIEW_WIDTH {
+        return Err(OutOfBoundsErr);
+    }
+
+    let index = column + VIEW_WIDTH * row;
+    let entry = read_entry_from_vga(index)?;
+    write_entry_to_vga(index, invert_colors(entry))
+}
+
+/// Swaps the foreground and background colors of a VGA entry.
+fn invert_colors(entry: u16) -> u16 {
+    let color = (entry >> 8) as u8;
+    let inverted = ((color & 0x07) << 4) | ((color >> 4) & 0x07);
+
+    ((inverted as u16) << 8) | (entry & 0xFF)
+}
+
 fn calculate_view_start_index(t: &Screen) -> usize {
     let mut rows: [(usize, usize); BUFFER_SIZE] = [(0, 0); BUFFER_SIZE];
     let mut index_rows = 0;
 
-    let mut current_line = (0, 0);
+    let mut current_line: (usize, usize) = (0, 0);
     for (i, e) in t.buffer.iter().enumerate() {
         if current_line == (0, 0) {
             current_line.0 = i;
         }
-        if (current_line.1 - current_line.0) == (VIEW_WIDTH - 1) {
+        // Right after a row is closed, the new row has a start but no end yet.
+        if current_line.1.wrapping_sub(current_line.0) == (VIEW_WIDTH - 1) {
             rows[index_rows] = current_line;
             index_rows += 1;
             current_line = (0, 0);
@@ -219,3 +285,45 @@ pub enum Color {
     /// White on Red
     Error = 0x4F,
 }
+
+#[cfg(test)]
+mod vga_test {
+    use super::*;
+
+    #[test]
+    fn test_view_start_after_newlines() {
+        let mut screen = Screen::default();
+
+        // Rows after a newline used to start with an end below their start, underflowing the width check.
+        screen.write_str("ab\ncd");
+        assert_eq!(calculate_view_start_index(&screen), 0);
+
+        for _ in 0..VIEW_HEIGHT {
+            screen.write_str("\nx");
+        }
+        assert_eq!(calculate_view_start_index(&screen), 6);
+    }
+
+    #[test]
+    fn test_entry_index_at() {
+        let mut screen = Screen::default();
+        screen.write_str("ab\ncd");
+
+        assert_eq!(entry_index_at(&screen, 1, 0), 1);
+        assert_eq!(entry_index_at(&screen, 2, 0), 2);
+        assert_eq!(entry_index_at(&screen, 40, 0), 2);
+        assert_eq!(entry_index_at(&screen, 1, 1), 4);
+        assert_eq!(entry_index_at(&screen, 2, 1), 5);
+        assert_eq!(entry_index_at(&screen, 0, 10), 5);
+    }
+
+    #[test]
+    fn test_invert_colors() {
+        assert_eq!(invert_colors(Entry::new(b'a').to_u16()), Entry::new_with_color(b'a', 0x70).to_u16());
+        assert_eq!(invert_colors(Entry::new_with_color(b'a', 0x70).to_u16()), Entry::new(b'a').to_u16());
+        assert_eq!(
+            invert_colors(Entry::new_with_color(b'!', Color::Error as u8).to_u16()),
+            Entry::new_with_color(b'!', 0x74).to_u16()
+        );
+    }
+}
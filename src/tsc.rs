@@ -0,0 +1,220 @@
+use core::{
+    arch::asm,
+    ops::{Add, Sub},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::__cpuid;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::__cpuid;
+
+use crate::{pit, rtc};
+
+/// CPUID leaf 1, EDX: the CPU has a time stamp counter.
+const CPUID_TSC: u32 = 1 << 4;
+
+/// Time the TSC is counted against the PIT during `calibrate`, in microseconds.
+const PIT_CALIBRATION_US: u32 = 50_000;
+
+const NANOS_PER_MICRO: u64 = 1000;
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+/// TSC frequency measured by `calibrate`, in kHz. 0 until then.
+static TSC_KHZ: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TscError {
+    /// CPUID does not report a time stamp counter.
+    Unsupported,
+    /// The counter did not advance during the calibration.
+    Stopped,
+}
+
+/// Reads the time stamp counter, the number of cycles since the CPU was reset.
+pub fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe { asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags)) };
+
+    (high as u64) << 32 | low as u64
+}
+
+/// Returns `true` if the CPU has a time stamp counter.
+pub fn is_supported() -> bool {
+    __cpuid(1).edx & CPUID_TSC != 0
+}
+
+/// Measures the TSC frequency, against the counter of the PIT if it is programmed or one RTC second otherwise.
+///
+/// ### Returns:
+/// - `Ok(u32)` with the frequency in kHz, from then on used by `Instant`.
+/// - `Err(TscError)` if there is no usable TSC, `Instant` then measures nothing.
+///
+/// ### Notes:
+/// - The PIT calibration takes 50ms and works with interrupts disabled. The RTC one takes up to two seconds.
+pub fn calibrate() -> Result<u32, TscError> {
+    if !is_supported() {
+        return Err(TscError::Unsupported);
+    }
+
+    let (cycles, micros) = if pit::is_running() {
+        let start = rdtsc();
+        pit::busy_wait_us(PIT_CALIBRATION_US);
+        (rdtsc() - start, PIT_CALIBRATION_US as u64)
+    } else {
+        rtc::wait_for_next_second();
+        let start = rdtsc();
+        rtc::wait_for_next_second();
+        (rdtsc() - start, 1_000_000)
+    };
+
+    let khz = (cycles * 1000 / micros) as u32;
+    if khz == 0 {
+        return Err(TscError::Stopped);
+    }
+
+    TSC_KHZ.store(khz, Ordering::Release);
+    Ok(khz)
+}
+
+/// Returns the TSC frequency found by `calibrate`, in kHz, or 0 if it was not calibrated.
+pub fn frequency_khz() -> u32 {
+    TSC_KHZ.load(Ordering::Acquire)
+}
+
+/// Converts a number of cycles at `khz` to nanoseconds, without overflowing for long intervals.
+fn cycles_to_nanos(cycles: u64, khz: u32) -> u64 {
+    let khz = khz as u64;
+
+    cycles / khz * NANOS_PER_MILLI + cycles % khz * NANOS_PER_MILLI / khz
+}
+
+/// A span of time with nanosecond precision.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Duration {
+    nanos: u64,
+}
+
+#[allow(dead_code)]
+impl Duration {
+    pub const ZERO: Duration = Duration { nanos: 0 };
+
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Duration { nanos }
+    }
+
+    pub const fn from_micros(micros: u64) -> Self {
+        Duration {
+            nanos: micros * NANOS_PER_MICRO,
+        }
+    }
+
+    pub const fn from_millis(millis: u64) -> Self {
+        Duration {
+            nanos: millis * NANOS_PER_MILLI,
+        }
+    }
+
+    pub const fn as_nanos(&self) -> u64 {
+        self.nanos
+    }
+
+    pub const fn as_micros(&self) -> u64 {
+        self.nanos / NANOS_PER_MICRO
+    }
+
+    pub const fn as_millis(&self) -> u64 {
+        self.nanos / NANOS_PER_MILLI
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        Duration { nanos: self.nanos + rhs.nanos }
+    }
+}
+
+/// Saturates at `Duration::ZERO` instead of underflowing.
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Duration) -> Duration {
+        Duration {
+            nanos: self.nanos.saturating_sub(rhs.nanos),
+        }
+    }
+}
+
+/// A point in time read from the TSC, to measure how long a section of code takes.
+///
+/// ### Example Usage:
+/// ```
+/// let start = Instant::now();
+/// t.flush();
+/// let spent = start.elapsed().as_micros();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant {
+    cycles: u64,
+}
+
+#[allow(dead_code)]
+impl Instant {
+    pub fn now() -> Self {
+        Instant { cycles: rdtsc() }
+    }
+
+    /// Returns the time elapsed since `earlier`, or `Duration::ZERO` if it is later than `self`
+    /// or the TSC was not calibrated.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        match frequency_khz() {
+            0 => Duration::ZERO,
+            khz => Duration::from_nanos(cycles_to_nanos(self.cycles.saturating_sub(earlier.cycles), khz)),
+        }
+    }
+
+    /// Returns the time elapsed since this instant was taken.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tsc_test {
+    use super::*;
+
+    #[test]
+    fn test_cycles_to_nanos() {
+        assert_eq!(cycles_to_nanos(1000, 1000), 1_000_000);
+        assert_eq!(cycles_to_nanos(3, 3_000_000), 1);
+        assert_eq!(cycles_to_nanos(1500, 3_000_000), 500);
+        // Ten days at 4 GHz, `cycles * 1_000_000` alone would overflow.
+        assert_eq!(cycles_to_nanos(3_456_000_000_000_000, 4_000_000), 864_000_000_000_000);
+    }
+
+    #[test]
+    fn test_duration_units() {
+        let d = Duration::from_millis(3) + Duration::from_micros(20);
+        assert_eq!(d.as_nanos(), 3_020_000);
+        assert_eq!(d.as_micros(), 3020);
+        assert_eq!(d.as_millis(), 3);
+        assert_eq!(Duration::from_nanos(5) - Duration::from_nanos(10), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_rdtsc_is_monotonic() {
+        let first = rdtsc();
+        assert!(rdtsc() >= first);
+    }
+}